@@ -0,0 +1,70 @@
+use crate::retry::RetryPolicy;
+use anyhow::{Context, Result, bail};
+use futures::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::{Client, ClientBuilder};
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+
+const USER_AGENT: &str = concat!(
+    "littlepenguin66/mcpacker/",
+    env!("CARGO_PKG_VERSION"),
+    " (https://github.com/littlepenguin66/mcpacker)"
+);
+
+/// Sends every request from `builder` through `proxy` when one is given.
+pub fn with_proxy(builder: ClientBuilder, proxy: Option<&str>) -> Result<ClientBuilder> {
+    let Some(proxy_url) = proxy else {
+        return Ok(builder);
+    };
+    let proxy = reqwest::Proxy::all(proxy_url)
+        .with_context(|| format!("Invalid proxy URL: {}", proxy_url))?;
+    Ok(builder.proxy(proxy))
+}
+
+/// A client for APIs that ask callers to identify themselves.
+pub fn build_client(proxy: Option<&str>) -> Result<Client> {
+    with_proxy(Client::builder().user_agent(USER_AGENT), proxy)?
+        .build()
+        .map_err(Into::into)
+}
+
+/// Streams `url` into `output_path` behind a progress bar, handing each chunk to `on_chunk`.
+pub async fn download_with_progress(
+    client: &Client,
+    retry: RetryPolicy,
+    url: &str,
+    output_path: &Path,
+    label: &str,
+    mut on_chunk: impl FnMut(&[u8]),
+) -> Result<()> {
+    let response = retry
+        .send(client.get(url))
+        .await
+        .with_context(|| format!("Failed to request {}", url))?;
+
+    if !response.status().is_success() {
+        bail!("Download failed for {}: {}", label, response.status());
+    }
+
+    let pb = ProgressBar::new(response.content_length().unwrap_or(0));
+    pb.set_style(ProgressStyle::with_template(
+        "{spinner:.green} [{elapsed_precise}] {bar:40.cyan/blue} {bytes}/{total_bytes} ({bytes_per_sec}, {eta}) {msg}",
+    )?.progress_chars("━╸ "));
+    pb.set_message(format!("Downloading: {}", label));
+
+    let mut stream = response.bytes_stream();
+    let mut file = File::create(output_path).await?;
+
+    while let Some(item) = stream.next().await {
+        let chunk = item.context("Failed to read chunk")?;
+        file.write_all(&chunk).await?;
+        on_chunk(&chunk);
+        pb.inc(chunk.len() as u64);
+    }
+    file.flush().await?;
+
+    pb.finish_with_message(format!("{} download complete", label));
+    Ok(())
+}
@@ -1,6 +1,11 @@
+mod http;
 mod models;
 mod ops;
 mod parsers;
+mod resolvers;
+mod retry;
+#[cfg(test)]
+mod test_support;
 mod ui;
 mod utils;
 
@@ -14,22 +19,32 @@ const LOGO: &str = r#"
 ╚═╝     ╚═╝ ╚═════╝╚═╝     ╚═╝  ╚═╝ ╚═════╝╚═╝  ╚═╝╚══════╝╚═╝  ╚═╝
 ==================================================================="#;
 
-use ops::{downloader, generator, installer};
+use ops::duplicates::{self, DuplicatePolicy};
+use ops::loader_check::{self, Compatibility};
+use ops::{client_filter, dependencies, downloader, generator, installer};
 
 use anyhow::{Context, Result};
-use clap::Parser;
-use std::path::PathBuf;
+use clap::{Parser, Subcommand};
+use indicatif::{HumanBytes, HumanDuration};
+use parsers::PackFormat;
+use parsers::filter::{KeywordMatch, KeywordMatcher};
+use retry::RetryPolicy;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use ui::{LOOKING_GLASS, SPARKLE, print_header, print_info, print_step, print_success, print_warn};
 
 #[derive(Parser, Debug)]
-#[command(author, version, about = LOGO, long_about = None)]
+#[command(author, version, about = LOGO, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     #[arg(
         index = 1,
-        required_unless_present = "update_list",
-        value_parser = verify_input_file
+        required_unless_present_any = ["update_list", "from_bundle"],
+        value_parser = verify_input
     )]
-    input: Option<PathBuf>,
+    input: Option<InputSource>,
     #[arg(short, long)]
     output: Option<PathBuf>,
     #[arg(
@@ -41,24 +56,119 @@ struct Args {
     memory: String,
     #[arg(long, default_value = "java")]
     java_path: String,
-    #[arg(short, long, default_value = "10", value_parser = verify_parallel_count)]
+    #[arg(global = true, short, long, default_value = "10", value_parser = verify_parallel_count)]
     parallel: usize,
     #[arg(long, short = 'u')]
     update_list: bool,
+    #[arg(long, default_value_t = 7)]
+    list_max_age: u32,
     #[arg(long)]
     keep_client: bool,
     #[arg(long)]
     filter_client: bool,
+    #[arg(long, value_enum, default_value_t = KeywordMatch::Token)]
+    keyword_match: KeywordMatch,
     #[arg(long)]
     accept_eula: bool,
     #[arg(long)]
     skip_hash: bool,
+    #[arg(global = true, long)]
+    allow_untrusted_hosts: bool,
     #[arg(long)]
     skip_installer_verify: bool,
     #[arg(long)]
-    installer_hash: Option<String>,
+    skip_dependency_check: bool,
+    #[arg(long)]
+    allow_loader_mismatch: bool,
+    #[arg(long, value_enum, default_value_t = DuplicatePolicy::Warn)]
+    duplicates: DuplicatePolicy,
+    #[arg(long)]
+    rules: Option<PathBuf>,
     #[arg(long)]
+    installer_hash: Option<String>,
+    #[arg(global = true, long)]
     proxy: Option<String>,
+    #[arg(global = true, long, default_value_t = retry::DEFAULT_RETRIES)]
+    retries: u32,
+    #[arg(global = true, long, default_value_t = retry::DEFAULT_RETRY_DELAY_MS)]
+    retry_delay: u64,
+    #[arg(long)]
+    offline: bool,
+    #[arg(long)]
+    from_bundle: Option<PathBuf>,
+    #[arg(global = true, long)]
+    game_version: Option<String>,
+    #[arg(global = true, long)]
+    loader: Option<String>,
+    #[arg(global = true, long, default_value = resolvers::modrinth::DEFAULT_API_BASE)]
+    modrinth_api: String,
+    #[arg(global = true, long, default_value = resolvers::curseforge::DEFAULT_API_BASE)]
+    curseforge_api: String,
+    #[arg(global = true, long)]
+    prefer_server_pack: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Inspect or clean the shared mod cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Download everything a pack needs into the local cache for a later --offline run
+    Prefetch {
+        #[arg(value_parser = verify_input)]
+        input: InputSource,
+    },
+    /// Pack a modpack and everything it needs into a tar for --from-bundle on an offline host
+    Bundle {
+        #[arg(value_parser = verify_input)]
+        input: InputSource,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheAction {
+    /// Show how many files the mod cache holds and their size
+    Stats,
+    /// Remove cached files not used within the given age, e.g. 30d or 12h
+    Prune {
+        #[arg(long, value_parser = verify_age)]
+        older_than: Duration,
+    },
+}
+
+const UNPACKED_MARKERS: [&str; 4] = [
+    "pack.toml",
+    "modrinth.index.json",
+    "manifest.json",
+    "mmc-pack.json",
+];
+
+#[derive(Debug, Clone)]
+enum InputSource {
+    Path(PathBuf),
+    Url(String),
+    Modrinth(resolvers::modrinth::ProjectSpec),
+    CurseForge(resolvers::curseforge::ProjectSpec),
+}
+
+fn verify_input(s: &str) -> Result<InputSource, String> {
+    if let Some(spec) = s.strip_prefix("modrinth:") {
+        return resolvers::modrinth::parse_spec(spec).map(InputSource::Modrinth);
+    }
+    if let Some(spec) = s.strip_prefix("curseforge:") {
+        return resolvers::curseforge::parse_spec(spec).map(InputSource::CurseForge);
+    }
+
+    if s.starts_with("http://") || s.starts_with("https://") {
+        url::Url::parse(s).map_err(|e| format!("Invalid input URL '{}': {}", s, e))?;
+        return Ok(InputSource::Url(s.to_string()));
+    }
+
+    verify_input_file(s).map(InputSource::Path)
 }
 
 fn verify_input_file(s: &str) -> Result<PathBuf, String> {
@@ -66,19 +176,121 @@ fn verify_input_file(s: &str) -> Result<PathBuf, String> {
     if !path.exists() {
         return Err(format!("File does not exist: {}", s));
     }
+    if path.is_dir() {
+        if !UNPACKED_MARKERS
+            .iter()
+            .any(|marker| path.join(marker).is_file())
+        {
+            return Err(format!(
+                "Directory is not an unpacked modpack (expected one of {}): {}",
+                UNPACKED_MARKERS.join(", "),
+                s
+            ));
+        }
+        return Ok(path);
+    }
     if !path.is_file() {
         return Err(format!("Path is not a file: {}", s));
     }
     let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
-    if ext != "zip" && ext != "mrpack" {
+    if ext != "zip" && ext != "mrpack" && ext != "toml" {
         return Err(format!(
-            "Unsupported file format: .{} (only .zip or .mrpack are supported)",
+            "Unsupported file format: .{} (only .zip, .mrpack or packwiz pack.toml are supported)",
             ext
         ));
     }
     Ok(path)
 }
 
+fn default_output_dir(input: &Path) -> Option<PathBuf> {
+    let input = input.canonicalize().unwrap_or_else(|_| input.to_path_buf());
+    if input.is_file() && !parsers::packwiz::is_packwiz_pack(&input) {
+        return input.file_stem().map(PathBuf::from);
+    }
+
+    let pack_dir = if input.is_dir() {
+        &input
+    } else {
+        input.parent()?
+    };
+    pack_dir
+        .file_name()
+        .map(|name| PathBuf::from(format!("{}-server", name.to_string_lossy())))
+}
+
+fn use_server_pack(prefer_server_pack: bool) -> Result<bool> {
+    if prefer_server_pack {
+        return Ok(true);
+    }
+
+    if !console::Term::stdout().is_term() {
+        print_warn(
+            "An official server pack is available; pass --prefer-server-pack to use it instead of converting the client pack.",
+        );
+        return Ok(false);
+    }
+
+    dialoguer::Confirm::new()
+        .with_prompt(
+            "An official server pack is available. Use it instead of converting the client pack?",
+        )
+        .default(true)
+        .interact()
+        .context("Failed to read server pack confirmation")
+}
+
+fn verify_age(s: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "Invalid age: '{}'. Please use formats like '30d', '12h' or '45m'",
+            s
+        )
+    };
+    let (number, unit) = match s.char_indices().last() {
+        Some((index, unit)) if unit.is_ascii_alphabetic() => (&s[..index], unit),
+        _ => (s, 'd'),
+    };
+    let seconds: u64 = match unit.to_ascii_lowercase() {
+        'd' => 86_400,
+        'h' => 3_600,
+        'm' => 60,
+        _ => return Err(invalid()),
+    };
+    let amount = number.parse::<u64>().map_err(|_| invalid())?;
+
+    amount
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+fn run_cache_command(action: &CacheAction) -> Result<()> {
+    match action {
+        CacheAction::Stats => {
+            let stats = ops::mod_cache::stats()?;
+            print_info("Location", &ops::mod_cache::cache_root()?.to_string_lossy());
+            print_info("Files", &stats.entries.to_string());
+            print_info("Size", &HumanBytes(stats.total_bytes).to_string());
+            if let Some(oldest) = stats.oldest {
+                let age = oldest.elapsed().unwrap_or_default();
+                print_info(
+                    "Least recently used",
+                    &format!("{} ago", HumanDuration(age)),
+                );
+            }
+        }
+        CacheAction::Prune { older_than } => {
+            let summary = ops::mod_cache::prune(*older_than)?;
+            print_success(&format!(
+                "Removed {} cached files ({})",
+                summary.removed,
+                HumanBytes(summary.freed_bytes)
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn verify_memory_format(s: &str) -> Result<String, String> {
     let Some((index, _)) = s.char_indices().last() else {
         return Err(format!(
@@ -116,81 +328,366 @@ fn verify_parallel_count(s: &str) -> Result<usize, String> {
     }
 }
 
+/// Turns the input argument into a local pack, downloading it first when it is remote.
+/// Also returns the official server pack when one was chosen instead of the client pack.
+async fn resolve_input(
+    args: &Args,
+    input: InputSource,
+    retry: RetryPolicy,
+) -> Result<(PathBuf, Option<PathBuf>)> {
+    if args.offline && !matches!(input, InputSource::Path(_)) {
+        anyhow::bail!(
+            "--offline needs a local pack file; run `mcpacker prefetch` on a connected machine and pass the pack it saves"
+        );
+    }
+
+    let mut server_pack = None;
+    let input = match input {
+        InputSource::Path(path) => path,
+        InputSource::Url(url) if resolvers::url::is_packwiz_url(&url) => {
+            print_step("Fetching packwiz pack");
+            parsers::packwiz::fetch_remote_pack(&url, args.proxy.as_deref(), retry, args.parallel)
+                .await?
+        }
+        InputSource::Url(url) => {
+            print_step("Downloading modpack");
+            resolvers::url::download_pack(&url, args.proxy.as_deref(), retry).await?
+        }
+        InputSource::Modrinth(spec) => {
+            print_step(&format!("Resolving Modrinth project {}", spec.slug));
+            resolvers::modrinth::resolve_modpack(
+                &args.modrinth_api,
+                &spec,
+                args.game_version.as_deref(),
+                args.loader.as_deref(),
+                args.proxy.as_deref(),
+                retry,
+            )
+            .await?
+        }
+        InputSource::CurseForge(spec) => {
+            print_step(&format!("Resolving CurseForge project {}", spec.project_id));
+            let resolved = resolvers::curseforge::resolve_modpack(
+                &args.curseforge_api,
+                &spec,
+                args.game_version.as_deref(),
+                args.loader.as_deref(),
+                args.proxy.as_deref(),
+                retry,
+            )
+            .await?;
+
+            if let Some(server_file_id) = resolved.server_pack_file_id
+                && use_server_pack(args.prefer_server_pack)?
+            {
+                print_step("Downloading official server pack");
+                server_pack = Some(
+                    resolvers::curseforge::download_server_pack(
+                        &args.curseforge_api,
+                        spec.project_id,
+                        server_file_id,
+                        args.proxy.as_deref(),
+                        retry,
+                    )
+                    .await?,
+                );
+            }
+            resolved.client_pack
+        }
+    };
+    Ok((input, server_pack))
+}
+
+async fn parse_pack(
+    args: &Args,
+    input: &Path,
+    pack_format: Option<PackFormat>,
+    rules: &parsers::rules::FilterRules,
+    retry: RetryPolicy,
+    keep_client: bool,
+    filter_client: bool,
+) -> Result<(models::ServerContext, Vec<models::ModInfo>)> {
+    let parsed = match pack_format {
+        None => {
+            print_step("Reading CurseForge manifest");
+            (parsers::curseforge::read_server_context(input)?, Vec::new())
+        }
+        Some(PackFormat::Packwiz) => {
+            print_step("Parsing packwiz Modpack");
+            parsers::packwiz::parse_packwiz(input, keep_client)?
+        }
+        Some(PackFormat::Modrinth) => {
+            print_step("Parsing Modrinth Modpack");
+            let (ctx, mods) = parsers::modrinth::parse_mrpack(
+                input,
+                keep_client,
+                args.allow_untrusted_hosts,
+                rules,
+            )?;
+            (ctx, mods)
+        }
+        Some(PackFormat::Prism) => {
+            print_step("Parsing Prism Launcher Instance");
+            parsers::prism::parse_prism(input, keep_client, rules)?
+        }
+        Some(PackFormat::CurseForge) => {
+            print_step("Parsing CurseForge Modpack");
+            let (ctx, mods) = parsers::curseforge::parse_curseforge(
+                input,
+                filter_client,
+                args.proxy.as_deref(),
+                retry,
+                args.offline,
+                rules,
+            )
+            .await?;
+            (ctx, mods)
+        }
+    };
+    Ok(parsed)
+}
+
+/// Warms every cache an `--offline` run reads: mods, the loader file, the client-only list
+/// and CurseForge file names. Returns the local pack with what was parsed from it.
+async fn prefetch_pack(
+    args: &Args,
+    input: InputSource,
+    retry: RetryPolicy,
+) -> Result<(PathBuf, models::ServerContext, Vec<models::ModInfo>)> {
+    print_step("Updating client-only mods list");
+    match parsers::filter::update_fallback_list(args.proxy.as_deref(), retry, false).await {
+        Ok(_) => print_success("Client-only mods list updated and cached."),
+        Err(e) => print_warn(&format!("Failed to update client-only mods list: {:#}", e)),
+    }
+
+    // Offline runs convert the client pack, so that is what gets cached even when a server pack exists.
+    let (input, _) = resolve_input(args, input, retry).await?;
+    let pack_format = Some(parsers::detect_format(&input)?);
+    let rules = parsers::rules::FilterRules::default();
+    let (context, mods) = parse_pack(args, &input, pack_format, &rules, retry, true, true).await?;
+
+    let staging = tempfile::Builder::new()
+        .prefix("mcpacker-prefetch-")
+        .tempdir()
+        .context("Failed to create a staging directory")?;
+    let result = async {
+        print_info("Mods found", &mods.len().to_string());
+        downloader::download_all(
+            mods.clone(),
+            staging.path().to_path_buf(),
+            args.parallel,
+            false,
+            args.proxy.as_deref(),
+            retry,
+            false,
+        )
+        .await?;
+
+        print_step("Caching Server Loader");
+        installer::fetch_loader_file(
+            &context,
+            staging.path(),
+            args.proxy.as_deref(),
+            retry,
+            false,
+        )
+        .await?;
+        installer::cache_server_install(
+            &context,
+            staging.path(),
+            &args.java_path,
+            args.skip_installer_verify,
+            args.installer_hash.as_deref(),
+        )
+        .await
+    }
+    .await;
+    let _ = staging.close();
+    result?;
+
+    Ok((input, context, mods))
+}
+
+async fn run_prefetch(args: &Args, input: InputSource, retry: RetryPolicy) -> Result<()> {
+    let (input, _, _) = prefetch_pack(args, input, retry).await?;
+
+    println!();
+    print_success(&format!("{} Pack is cached for offline use.", SPARKLE));
+    println!(
+        "   Run {} without a network connection.",
+        ui::style(format!("mcpacker --offline {}", input.display())).cyan()
+    );
+    Ok(())
+}
+
+async fn run_bundle(
+    args: &Args,
+    input: InputSource,
+    output: &Path,
+    retry: RetryPolicy,
+) -> Result<()> {
+    let (input, context, mods) = prefetch_pack(args, input, retry).await?;
+
+    print_step("Writing offline bundle");
+    let mut cache_files = installer::cached_loader_files(&context)?;
+    cache_files.extend(parsers::filter::cached_list_paths());
+    let file_names = parsers::curseforge::file_name_cache_path()?;
+    if file_names.is_file() {
+        cache_files.push(file_names);
+    }
+    let written = ops::bundle::write(output, &input, &mods, &cache_files)?;
+
+    println!();
+    print_success(&format!(
+        "{} Bundled {} files into {}",
+        SPARKLE,
+        written,
+        output.display()
+    ));
+    println!(
+        "   Run {} on the offline host.",
+        ui::style(format!("mcpacker --from-bundle {}", output.display())).cyan()
+    );
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
 
     ui::print_logo();
 
-    let input_extension = args
-        .input
-        .as_ref()
-        .and_then(|path| path.extension())
-        .and_then(|ext| ext.to_str());
-    let needs_filter_cache =
-        args.update_list || (args.filter_client && input_extension == Some("zip"));
+    if let Some(Command::Cache { action }) = &args.command {
+        return run_cache_command(action);
+    }
+
+    let retry = RetryPolicy::new(args.retries, Duration::from_millis(args.retry_delay));
+    if let Some(Command::Prefetch { input }) = &args.command {
+        return run_prefetch(&args, input.clone(), retry).await;
+    }
+    if let Some(Command::Bundle { input, output }) = &args.command {
+        return run_bundle(&args, input.clone(), output, retry).await;
+    }
+
+    if let Some(bundle) = &args.from_bundle {
+        print_step("Importing offline bundle");
+        let pack = match &args.input {
+            Some(InputSource::Path(path)) => Some(path.as_path()),
+            _ => None,
+        };
+        let imported = ops::bundle::import(bundle, pack)?;
+        print_success(&format!(
+            "Verified and cached {} bundled files.",
+            imported.files
+        ));
+        // A remote input would point at a cache path from the connected machine.
+        if !matches!(args.input, Some(InputSource::Path(_))) {
+            print_info("Bundled pack", &imported.pack.to_string_lossy());
+            args.input = Some(InputSource::Path(imported.pack));
+        }
+        args.offline = true;
+    }
+
+    if let Err(e) = parsers::filter::migrate_legacy_cache() {
+        print_warn(&format!(
+            "Failed to migrate the old client-only mods list: {:#}",
+            e
+        ));
+    }
+
+    let needs_filter_cache = args.update_list || args.filter_client;
     let cache_exists = parsers::filter::is_cache_present();
-    if needs_filter_cache && (args.update_list || !cache_exists) {
+    let cache_age = parsers::filter::cache_age();
+    let cache_stale = cache_exists
+        && cache_age.is_none_or(|age| {
+            chrono::TimeDelta::try_days(args.list_max_age.into()).is_none_or(|max| age > max)
+        });
+    if needs_filter_cache && (args.update_list || !cache_exists || (cache_stale && !args.offline)) {
         if !cache_exists {
             print_step("No mods list cache found. Performing initial update...");
-        } else {
+        } else if args.update_list {
             print_step("Updating client-only mods list");
+        } else if cache_age.is_none() {
+            print_step("Client-only mods list age is unknown. Refreshing...");
+        } else {
+            print_step(&format!(
+                "Client-only mods list is older than {} days. Refreshing...",
+                args.list_max_age
+            ));
+        }
+        match parsers::filter::update_fallback_list(args.proxy.as_deref(), retry, args.offline)
+            .await
+        {
+            Ok(updates) => {
+                for update in &updates {
+                    if let Ok(count) = &update.result {
+                        print_info(&update.name, &format!("{} keywords", count));
+                    }
+                }
+                print_success("Client-only mods list updated and cached.");
+            }
+            Err(e) if cache_exists && !args.update_list => {
+                print_warn(&format!(
+                    "Failed to refresh client-only mods list, using the cached copy: {:#}",
+                    e
+                ));
+            }
+            Err(e) => return Err(e),
         }
-        parsers::filter::update_fallback_list(args.proxy.as_deref()).await?;
-        print_success("Client-only mods list updated and cached.");
         if args.update_list && args.input.is_none() {
             return Ok(());
         }
     }
 
-    let input = args
-        .input
-        .context("Missing input file. Use --help for usage.")?;
+    let (input, server_pack) = resolve_input(
+        &args,
+        args.input
+            .clone()
+            .context("Missing input file. Use --help for usage.")?,
+        retry,
+    )
+    .await?;
 
-    let output = match args.output {
+    let output = match args.output.clone() {
         Some(path) => path,
-        None => {
-            let stem = input
-                .file_stem()
-                .context("Cannot derive output folder name from input file")?;
-            PathBuf::from(stem)
-        }
+        None => default_output_dir(&input)
+            .context("Cannot derive output folder name from input file")?,
     };
 
     print_header("McPacker - ServerPack Generator");
     print_info("Input", &input.to_string_lossy());
     print_info("Output", &output.to_string_lossy());
+    if args.filter_client {
+        let age = parsers::filter::cache_age()
+            .map(|age| format!("updated {} ago", parsers::filter::format_age(age)))
+            .unwrap_or_else(|| "unknown".to_string());
+        print_info("Client-only list", &age);
+    }
     if args.skip_installer_verify {
         print_warn("Installer hash verification disabled; use with caution.");
     } else if let Some(expected) = args.installer_hash.as_deref() {
         print_info("Installer hash (expected)", expected);
     }
 
-    let extension = input
-        .extension()
-        .and_then(|e| e.to_str())
-        .context("Failed to determine file extension")?;
+    let rules = parsers::rules::FilterRules::load(args.rules.as_deref())?;
+    if let Some(path) = &args.rules {
+        print_info("Rules", &path.to_string_lossy());
+    }
 
-    let (context, mods) = match extension {
-        "mrpack" => {
-            print_step("Parsing Modrinth Modpack");
-            let (ctx, mods) = parsers::modrinth::parse_mrpack(&input, args.keep_client)?;
-            (ctx, mods)
-        }
-        "zip" => {
-            print_step("Parsing CurseForge Modpack");
-            let (ctx, mods) = parsers::curseforge::parse_curseforge(
-                &input,
-                args.filter_client,
-                args.proxy.as_deref(),
-            )
-            .await?;
-            (ctx, mods)
-        }
-        ext => {
-            anyhow::bail!("Unsupported file extension: .{}", ext);
-        }
+    let pack_format = match server_pack {
+        Some(_) => None,
+        None => Some(parsers::detect_format(&input)?),
     };
+    let (context, mods) = parse_pack(
+        &args,
+        &input,
+        pack_format,
+        &rules,
+        retry,
+        args.keep_client,
+        args.filter_client,
+    )
+    .await?;
 
     println!(
         "{} Server: {} | Loader: {:?} {}",
@@ -199,25 +696,196 @@ async fn main() -> Result<()> {
         context.loader_type,
         ui::style(&context.loader_version).bold()
     );
-    print_info("Mods found", &mods.len().to_string());
+    if args.offline {
+        installer::check_offline_loader(&context)?;
+    }
+    if let Some(server_pack) = &server_pack {
+        print_step("Extracting official server pack");
+        generator::extract_server_pack(server_pack, &output).await?;
+    } else {
+        print_info("Mods found", &mods.len().to_string());
+        let pinned_mods = rules.kept_file_names(&mods);
+        let keywords = if args.filter_client {
+            Some(KeywordMatcher::new(
+                parsers::filter::client_only_keywords(),
+                args.keyword_match,
+            )?)
+        } else {
+            None
+        };
 
-    downloader::download_all(
-        mods,
-        output.clone(),
-        args.parallel,
-        args.skip_hash,
+        let final_names = downloader::download_all(
+            mods,
+            output.clone(),
+            args.parallel,
+            args.skip_hash,
+            args.proxy.as_deref(),
+            retry,
+            args.offline,
+        )
+        .await?;
+
+        // Without --filter-client the rules still apply; only the keyword and side checks are skipped.
+        if keywords.is_some() || !rules.is_empty() {
+            let pinned = final_names
+                .into_iter()
+                .filter(|(pack_name, _)| pinned_mods.contains(pack_name))
+                .map(|(_, final_name)| final_name)
+                .collect();
+            match &keywords {
+                Some(keywords) => {
+                    print_step("Checking for client-only mods");
+                    if keywords.is_empty() {
+                        print_warn(
+                            "Client-only filter list is empty; only jar metadata will be used.",
+                        );
+                    }
+                }
+                None => print_step("Applying filter rules"),
+            }
+
+            let modrinth_sides = if keywords.is_some()
+                && pack_format == Some(PackFormat::CurseForge)
+                && !args.offline
+            {
+                match client_filter::modrinth_sides(
+                    &output,
+                    &args.modrinth_api,
+                    args.proxy.as_deref(),
+                    retry,
+                )
+                .await
+                {
+                    Ok(sides) => {
+                        print_info("Modrinth matches", &sides.len().to_string());
+                        sides
+                    }
+                    Err(e) => {
+                        print_warn(&format!(
+                            "Modrinth lookup failed; using jar metadata and keywords only: {:#}",
+                            e
+                        ));
+                        HashMap::new()
+                    }
+                }
+            } else {
+                HashMap::new()
+            };
+
+            let filter = client_filter::ClientFilter {
+                keywords,
+                rules,
+                pinned,
+                modrinth_sides,
+            };
+            let moved = client_filter::move_client_only_jars(&output, filter).await?;
+            for jar in &moved {
+                print_info(
+                    "Client-only",
+                    &format!("{} ({})", jar.file_name, jar.reason),
+                );
+            }
+            if !moved.is_empty() {
+                print_success(&format!(
+                    "Moved {} client-only mods to {}/",
+                    moved.len(),
+                    client_filter::CLIENT_MODS_DIR
+                ));
+            }
+        }
+    }
+
+    let index_jars = duplicates::snapshot_jars(&output);
+    if server_pack.is_none() {
+        print_step("Extracting overrides");
+        generator::extract_overrides(&input, &output, &index_jars).await?;
+    }
+
+    // Mods are checked before the loader is installed, so a failure leaves no half-built server.
+    print_step("Checking for duplicate mods");
+    let groups = duplicates::find_duplicates(&output, index_jars).await?;
+    if !groups.is_empty() {
+        for group in &groups {
+            print_warn(&format!("Duplicate mod {}", group));
+        }
+        match args.duplicates {
+            DuplicatePolicy::Fail => anyhow::bail!(
+                "{} mods are installed more than once; rerun with --duplicates keep-newest or --duplicates keep-index",
+                groups.len()
+            ),
+            DuplicatePolicy::Warn => print_warn(
+                "Keeping every copy; pass --duplicates keep-newest or --duplicates keep-index to keep one",
+            ),
+            policy => {
+                duplicates::move_duplicates(&output, &groups, policy)?;
+                print_success(&format!(
+                    "Kept one jar per mod and moved the rest to {}/",
+                    duplicates::DUPLICATE_MODS_DIR
+                ));
+            }
+        }
+    }
+
+    print_step("Checking mod loaders");
+    let mismatches = loader_check::check_loaders(&output, &context).await?;
+    let (incompatible, questionable): (Vec<_>, Vec<_>) = mismatches
+        .iter()
+        .partition(|mismatch| mismatch.compatibility == Compatibility::Incompatible);
+    for mismatch in &questionable {
+        print_warn(&mismatch.to_string());
+    }
+    if !incompatible.is_empty() {
+        if !args.allow_loader_mismatch {
+            let list = incompatible
+                .iter()
+                .map(|mismatch| format!("  - {}", mismatch))
+                .collect::<Vec<_>>()
+                .join("\n");
+            anyhow::bail!(
+                "{} mods do not match the {:?} server loader:\n{}\nRemove them or rerun with --allow-loader-mismatch.",
+                incompatible.len(),
+                context.loader_type,
+                list
+            );
+        }
+        for mismatch in &incompatible {
+            print_warn(&mismatch.to_string());
+        }
+    }
+
+    if !args.skip_dependency_check {
+        print_step("Checking mod dependencies");
+        let problems = dependencies::check_dependencies(&output).await?;
+        if !problems.is_empty() {
+            let list = problems
+                .iter()
+                .map(|problem| format!("  - {}", problem))
+                .collect::<Vec<_>>()
+                .join("\n");
+            anyhow::bail!(
+                "{} mod dependency problems found:\n{}\nFix the pack or rerun with --skip-dependency-check.",
+                problems.len(),
+                list
+            );
+        }
+    }
+
+    print_step("Installing Server Loader");
+    installer::fetch_loader_file(
+        &context,
+        &output,
         args.proxy.as_deref(),
+        retry,
+        args.offline,
     )
     .await?;
-
-    print_step("Installing Server Loader");
     let server_jar = installer::install_loader(
         &context,
         &output,
         &args.java_path,
         args.skip_installer_verify,
         args.installer_hash.as_deref(),
-        args.proxy.as_deref(),
+        args.offline,
     )
     .await?;
     print_success(&format!("Loader installed: {}", server_jar));
@@ -225,7 +893,6 @@ async fn main() -> Result<()> {
     print_step("Generating Configuration");
     let script_name = generator::generate_server_files(
         &context,
-        &input,
         &output,
         &args.memory,
         &server_jar,
@@ -247,8 +914,14 @@ async fn main() -> Result<()> {
 
 #[cfg(test)]
 mod tests {
-    use super::{Args, verify_memory_format, verify_parallel_count};
+    use super::{
+        Args, CacheAction, Command, DuplicatePolicy, InputSource, default_output_dir, verify_age,
+        verify_input, verify_memory_format, verify_parallel_count,
+    };
+    use crate::test_support::temp_dir;
     use clap::Parser;
+    use std::path::PathBuf;
+    use std::time::Duration;
 
     #[test]
     fn accepts_numeric_memory_values_with_supported_units() {
@@ -268,9 +941,143 @@ mod tests {
         assert!(Args::try_parse_from(["mcpacker", "--update-list", "--parallel", "0"]).is_err());
     }
 
+    #[test]
+    fn rejects_negative_and_overflowing_list_max_age() {
+        assert!(
+            Args::try_parse_from(["mcpacker", "--update-list", "--list-max-age", "-1"]).is_err()
+        );
+        assert!(
+            Args::try_parse_from([
+                "mcpacker",
+                "--update-list",
+                "--list-max-age",
+                "9999999999999"
+            ])
+            .is_err()
+        );
+        let args =
+            Args::try_parse_from(["mcpacker", "--update-list", "--list-max-age", "4294967295"])
+                .unwrap();
+        assert!(chrono::TimeDelta::try_days(args.list_max_age.into()).is_some());
+    }
+
+    #[test]
+    fn warns_about_duplicates_unless_a_policy_is_chosen() {
+        let args = Args::try_parse_from(["mcpacker", "--update-list"]).unwrap();
+        assert_eq!(args.duplicates, DuplicatePolicy::Warn);
+
+        let args =
+            Args::try_parse_from(["mcpacker", "--update-list", "--duplicates", "fail"]).unwrap();
+        assert_eq!(args.duplicates, DuplicatePolicy::Fail);
+    }
+
+    #[test]
+    fn takes_the_input_from_the_bundle_when_none_is_given() {
+        let args = Args::try_parse_from(["mcpacker", "--from-bundle", "bundle.tar"]).unwrap();
+
+        assert!(args.input.is_none());
+        assert_eq!(args.from_bundle, Some(PathBuf::from("bundle.tar")));
+        assert!(Args::try_parse_from(["mcpacker", "--offline"]).is_err());
+    }
+
     #[test]
     fn accepts_positive_parallel_downloads() {
         assert_eq!(verify_parallel_count("1"), Ok(1));
         assert_eq!(verify_parallel_count("12"), Ok(12));
     }
+
+    #[test]
+    fn parses_cache_subcommands_without_an_input() {
+        let args =
+            Args::try_parse_from(["mcpacker", "cache", "prune", "--older-than", "30d"]).unwrap();
+
+        assert!(matches!(
+            args.command,
+            Some(Command::Cache {
+                action: CacheAction::Prune { older_than }
+            }) if older_than == Duration::from_secs(30 * 86_400)
+        ));
+        assert_eq!(verify_age("12h"), Ok(Duration::from_secs(12 * 3_600)));
+        assert_eq!(verify_age("7"), Ok(Duration::from_secs(7 * 86_400)));
+        assert!(verify_age("soon").is_err());
+        assert!(verify_age("3w").is_err());
+        assert!(verify_age("300000000000000d").is_err());
+    }
+
+    #[test]
+    fn parses_prefetch_with_global_network_options() {
+        let root = temp_dir();
+        let pack = root.path().join("pack.mrpack");
+        std::fs::write(&pack, "").unwrap();
+
+        let args = Args::try_parse_from([
+            "mcpacker",
+            "prefetch",
+            &pack.to_string_lossy(),
+            "--proxy",
+            "http://127.0.0.1:8080",
+            "--retries",
+            "5",
+        ])
+        .unwrap();
+
+        assert!(matches!(
+            args.command,
+            Some(Command::Prefetch {
+                input: InputSource::Path(_)
+            })
+        ));
+        assert_eq!(args.proxy.as_deref(), Some("http://127.0.0.1:8080"));
+        assert_eq!(args.retries, 5);
+        assert!(!args.offline);
+    }
+
+    #[test]
+    fn accepts_http_urls_as_input() {
+        assert!(matches!(
+            verify_input("https://example.com/pack/pack.toml"),
+            Ok(InputSource::Url(_))
+        ));
+        assert!(matches!(
+            verify_input("http://example.com/download?file=pack"),
+            Ok(InputSource::Url(_))
+        ));
+        assert!(verify_input("https://").is_err());
+    }
+
+    #[test]
+    fn keeps_server_output_apart_from_unpacked_pack_directories() {
+        let root = temp_dir();
+        let root = root.path();
+        let pack_dir = root.join("my-pack");
+        std::fs::create_dir_all(&pack_dir).unwrap();
+        std::fs::write(pack_dir.join("modrinth.index.json"), "{}").unwrap();
+        std::fs::write(root.join("my-pack.mrpack"), "").unwrap();
+
+        assert_eq!(default_output_dir(&pack_dir), Some("my-pack-server".into()));
+        assert_eq!(
+            default_output_dir(&root.join("my-pack.mrpack")),
+            Some("my-pack".into())
+        );
+        assert!(verify_input(&pack_dir.to_string_lossy()).is_ok());
+        assert!(verify_input(&root.to_string_lossy()).is_err());
+    }
+
+    #[test]
+    fn accepts_modrinth_project_inputs() {
+        assert!(matches!(
+            verify_input("modrinth:better-mc@v26"),
+            Ok(InputSource::Modrinth(_))
+        ));
+        assert!(verify_input("modrinth:").is_err());
+    }
+
+    #[test]
+    fn accepts_curseforge_project_inputs() {
+        assert!(matches!(
+            verify_input("curseforge:123456/7890"),
+            Ok(InputSource::CurseForge(_))
+        ));
+        assert!(verify_input("curseforge:better-mc").is_err());
+    }
 }
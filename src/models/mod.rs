@@ -1,8 +1,13 @@
+pub mod version;
+
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
-use std::io::Read;
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::path::PathBuf;
 
 use crate::utils::sanitize_filename;
+use version::VersionReq;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum SideType {
@@ -20,6 +25,7 @@ pub struct ModInfo {
     pub hash_algo: String,
     pub side: SideType,
     pub is_required: bool,
+    pub install_path: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -42,6 +48,24 @@ pub struct ModMetadata {
     pub name: Option<String>,
     pub version: Option<String>,
     pub mod_id: Option<String>,
+    pub side: Option<SideType>,
+    pub provides: Vec<String>,
+    pub dependencies: Vec<Dependency>,
+    pub loaders: Vec<LoaderType>,
+}
+
+const LOADER_METADATA_FILES: [(&str, LoaderType); 5] = [
+    ("fabric.mod.json", LoaderType::Fabric),
+    ("quilt.mod.json", LoaderType::Quilt),
+    ("META-INF/mods.toml", LoaderType::Forge),
+    ("mcmod.info", LoaderType::Forge),
+    ("META-INF/neoforge.mods.toml", LoaderType::NeoForge),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub mod_id: String,
+    pub version: VersionReq,
 }
 
 #[derive(Debug, Deserialize)]
@@ -63,12 +87,56 @@ struct FabricModJson {
     name: Option<String>,
     #[serde(default)]
     version: Option<String>,
+    #[serde(default)]
+    environment: Option<String>,
+    #[serde(default)]
+    provides: Vec<String>,
+    #[serde(default)]
+    depends: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QuiltModJson {
+    quilt_loader: QuiltLoader,
+    #[serde(default)]
+    minecraft: Option<QuiltMinecraft>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QuiltLoader {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    metadata: Option<QuiltMetadata>,
+    #[serde(default)]
+    provides: Vec<serde_json::Value>,
+    #[serde(default)]
+    depends: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QuiltMetadata {
+    #[serde(default)]
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QuiltMinecraft {
+    #[serde(default)]
+    environment: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
+#[allow(non_snake_case)]
 struct ModsToml {
     #[serde(default)]
     mods: Vec<ModsTomlMod>,
+    #[serde(default)]
+    dependencies: HashMap<String, Vec<ModsTomlDependency>>,
+    #[serde(default)]
+    clientSideOnly: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -80,32 +148,65 @@ struct ModsTomlMod {
     displayName: Option<String>,
     #[serde(default)]
     version: Option<String>,
+    #[serde(default)]
+    displayTest: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct ModsTomlDependency {
+    #[serde(default)]
+    modId: Option<String>,
+    #[serde(default)]
+    side: Option<String>,
+    #[serde(default)]
+    mandatory: Option<bool>,
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    versionRange: Option<String>,
 }
 
 impl ModMetadata {
     pub fn extract_from_jar(jar_path: &std::path::Path) -> anyhow::Result<Self> {
-        use std::fs::File;
-        use zip::ZipArchive;
-
-        let file = File::open(jar_path)
+        let file = std::fs::File::open(jar_path)
             .with_context(|| format!("Failed to open jar file: {:?}", jar_path))?;
-        let mut archive = ZipArchive::new(file)
-            .with_context(|| format!("Failed to parse zip archive: {:?}", jar_path))?;
+
+        Self::extract_from_reader(file)
+            .with_context(|| format!("Failed to parse zip archive: {:?}", jar_path))
+    }
+
+    pub fn extract_from_reader<R: Read + Seek>(reader: R) -> anyhow::Result<Self> {
+        let mut archive = zip::ZipArchive::new(reader)?;
 
         let mut metadata = ModMetadata::default();
 
-        if let Ok(fabric) = Self::try_read_fabric_mod_json(&mut archive) {
+        if let Ok(quilt) = Self::try_read_quilt_mod_json(&mut archive) {
+            metadata = quilt;
+        } else if let Ok(fabric) = Self::try_read_fabric_mod_json(&mut archive) {
             metadata = fabric;
-        } else if let Ok(toml) = Self::try_read_mods_toml(&mut archive) {
+        } else if let Ok(toml) =
+            Self::try_read_mods_toml(&mut archive, "META-INF/neoforge.mods.toml")
+        {
+            metadata = toml;
+        } else if let Ok(toml) = Self::try_read_mods_toml(&mut archive, "META-INF/mods.toml") {
             metadata = toml;
         } else if let Ok(mcmod) = Self::try_read_mcmod_info(&mut archive) {
             metadata = mcmod;
         }
 
+        for (file_name, loader) in LOADER_METADATA_FILES {
+            if archive.index_for_name(file_name).is_some() && !metadata.loaders.contains(&loader) {
+                metadata.loaders.push(loader);
+            }
+        }
+
         Ok(metadata)
     }
 
-    fn try_read_mcmod_info(archive: &mut zip::ZipArchive<std::fs::File>) -> anyhow::Result<Self> {
+    fn try_read_mcmod_info(
+        archive: &mut zip::ZipArchive<impl Read + Seek>,
+    ) -> anyhow::Result<Self> {
         let mut entry = archive.by_name("mcmod.info")?;
         let mut content = String::new();
         entry.read_to_string(&mut content)?;
@@ -127,6 +228,10 @@ impl ModMetadata {
                 name: parsed.name,
                 version: parsed.version,
                 mod_id: parsed.modid,
+                side: None,
+                provides: Vec::new(),
+                dependencies: Vec::new(),
+                loaders: Vec::new(),
             })
         } else {
             anyhow::bail!("Failed to parse mcmod.info")
@@ -134,7 +239,7 @@ impl ModMetadata {
     }
 
     fn try_read_fabric_mod_json(
-        archive: &mut zip::ZipArchive<std::fs::File>,
+        archive: &mut zip::ZipArchive<impl Read + Seek>,
     ) -> anyhow::Result<Self> {
         let mut entry = archive.by_name("fabric.mod.json")?;
         let mut content = String::new();
@@ -146,11 +251,58 @@ impl ModMetadata {
             name: parsed.name.or(parsed.id.clone()),
             version: parsed.version,
             mod_id: parsed.id,
+            side: parsed
+                .environment
+                .as_deref()
+                .and_then(side_from_environment),
+            provides: parsed.provides,
+            dependencies: parsed
+                .depends
+                .into_iter()
+                .map(|(mod_id, versions)| Dependency {
+                    mod_id,
+                    version: version_req_from_json(&versions),
+                })
+                .collect(),
+            loaders: Vec::new(),
+        })
+    }
+
+    fn try_read_quilt_mod_json(
+        archive: &mut zip::ZipArchive<impl Read + Seek>,
+    ) -> anyhow::Result<Self> {
+        let mut entry = archive.by_name("quilt.mod.json")?;
+        let mut content = String::new();
+        entry.read_to_string(&mut content)?;
+
+        let parsed: QuiltModJson = json5::from_str(&content)?;
+        let loader = parsed.quilt_loader;
+
+        Ok(ModMetadata {
+            name: loader.metadata.and_then(|m| m.name).or(loader.id.clone()),
+            version: loader.version,
+            mod_id: loader.id,
+            side: parsed
+                .minecraft
+                .and_then(|minecraft| minecraft.environment)
+                .as_deref()
+                .and_then(side_from_environment),
+            provides: loader
+                .provides
+                .iter()
+                .filter_map(quilt_entry_id)
+                .map(str::to_string)
+                .collect(),
+            dependencies: loader.depends.iter().filter_map(quilt_dependency).collect(),
+            loaders: Vec::new(),
         })
     }
 
-    fn try_read_mods_toml(archive: &mut zip::ZipArchive<std::fs::File>) -> anyhow::Result<Self> {
-        let mut entry = archive.by_name("META-INF/mods.toml")?;
+    fn try_read_mods_toml(
+        archive: &mut zip::ZipArchive<impl Read + Seek>,
+        entry_name: &str,
+    ) -> anyhow::Result<Self> {
+        let mut entry = archive.by_name(entry_name)?;
         let mut content = String::new();
         entry.read_to_string(&mut content)?;
 
@@ -161,9 +313,18 @@ impl ModMetadata {
                 name: mod_info.displayName.clone().or(mod_info.modId.clone()),
                 version: mod_info.version.clone(),
                 mod_id: mod_info.modId.clone(),
+                side: side_from_mods_toml(&parsed, mod_info),
+                provides: parsed
+                    .mods
+                    .iter()
+                    .skip(1)
+                    .filter_map(|other| other.modId.clone())
+                    .collect(),
+                dependencies: mods_toml_dependencies(&parsed),
+                loaders: Vec::new(),
             })
         } else {
-            anyhow::bail!("No mod info found in mods.toml")
+            anyhow::bail!("No mod info found in {}", entry_name)
         }
     }
 
@@ -182,3 +343,336 @@ impl ModMetadata {
             .unwrap_or_else(|| "unknown".to_string())
     }
 }
+
+fn side_from_environment(environment: &str) -> Option<SideType> {
+    match environment {
+        "client" => Some(SideType::Client),
+        "server" | "dedicated_server" => Some(SideType::Server),
+        "*" => Some(SideType::Both),
+        _ => None,
+    }
+}
+
+fn side_from_mods_toml(parsed: &ModsToml, mod_info: &ModsTomlMod) -> Option<SideType> {
+    if parsed.clientSideOnly {
+        return Some(SideType::Client);
+    }
+
+    let platform_sides = mod_info
+        .modId
+        .as_ref()
+        .and_then(|mod_id| parsed.dependencies.get(mod_id))
+        .into_iter()
+        .flatten()
+        .filter(|dependency| {
+            matches!(
+                dependency.modId.as_deref(),
+                Some("minecraft" | "forge" | "neoforge")
+            )
+        })
+        .filter_map(|dependency| dependency.side.as_deref());
+
+    let mut side = None;
+    for declared in platform_sides {
+        match declared.to_ascii_uppercase().as_str() {
+            "CLIENT" => return Some(SideType::Client),
+            "SERVER" => side = Some(SideType::Server),
+            "BOTH" => side = side.or(Some(SideType::Both)),
+            _ => {}
+        }
+    }
+    side.or_else(|| side_from_display_test(mod_info.displayTest.as_deref()?))
+}
+
+/// `displayTest` only says which side may lack the mod, so it is a fallback after dependency sides.
+/// `IGNORE_SERVER_VERSION` marks server-only mods. `IGNORE_ALL_VERSION` is shared by client-side
+/// tweaks and optional mods that work on either side, so it says nothing about the server.
+fn side_from_display_test(display_test: &str) -> Option<SideType> {
+    display_test
+        .eq_ignore_ascii_case("IGNORE_SERVER_VERSION")
+        .then_some(SideType::Server)
+}
+
+fn version_req_from_json(versions: &serde_json::Value) -> VersionReq {
+    let predicates: Vec<String> = match versions {
+        serde_json::Value::String(predicate) => vec![predicate.clone()],
+        serde_json::Value::Array(predicates) => predicates
+            .iter()
+            .filter_map(|predicate| predicate.as_str().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    if predicates.is_empty() || predicates.iter().any(|predicate| predicate.trim() == "*") {
+        VersionReq::Any
+    } else {
+        VersionReq::Predicates(predicates)
+    }
+}
+
+fn quilt_entry_id(entry: &serde_json::Value) -> Option<&str> {
+    entry
+        .as_str()
+        .or_else(|| entry.get("id").and_then(|id| id.as_str()))
+}
+
+fn quilt_dependency(entry: &serde_json::Value) -> Option<Dependency> {
+    if entry
+        .get("optional")
+        .and_then(|optional| optional.as_bool())
+        == Some(true)
+    {
+        return None;
+    }
+
+    Some(Dependency {
+        mod_id: quilt_entry_id(entry)?.to_string(),
+        version: entry
+            .get("versions")
+            .map(version_req_from_json)
+            .unwrap_or(VersionReq::Any),
+    })
+}
+
+fn mods_toml_dependencies(parsed: &ModsToml) -> Vec<Dependency> {
+    let own_ids: Vec<&str> = parsed
+        .mods
+        .iter()
+        .filter_map(|mod_info| mod_info.modId.as_deref())
+        .collect();
+
+    own_ids
+        .iter()
+        .filter_map(|mod_id| parsed.dependencies.get(*mod_id))
+        .flatten()
+        .filter(|dependency| match dependency.kind.as_deref() {
+            Some(kind) => kind.eq_ignore_ascii_case("required"),
+            None => dependency.mandatory.unwrap_or(false),
+        })
+        // The server never loads client-side dependencies, and the client filter may have moved them.
+        .filter(|dependency| {
+            !dependency
+                .side
+                .as_deref()
+                .is_some_and(|side| side.eq_ignore_ascii_case("CLIENT"))
+        })
+        .filter_map(|dependency| {
+            let mod_id = dependency.modId.clone()?;
+            if own_ids.contains(&mod_id.as_str()) {
+                return None;
+            }
+
+            let version = match dependency.versionRange.as_deref().map(str::trim) {
+                None | Some("") | Some("*") => VersionReq::Any,
+                Some(range) => VersionReq::MavenRange(range.to_string()),
+            };
+            Some(Dependency { mod_id, version })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::version::VersionReq;
+    use super::{Dependency, ModMetadata, SideType};
+    use std::io::{Cursor, Write};
+
+    fn jar(entries: &[(&str, &str)]) -> Cursor<Vec<u8>> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        let mut cursor = writer.finish().unwrap();
+        cursor.set_position(0);
+        cursor
+    }
+
+    #[test]
+    fn reads_quilt_mod_json() {
+        let metadata = ModMetadata::extract_from_reader(jar(&[(
+            "quilt.mod.json",
+            include_str!("fixtures/quilt.mod.json"),
+        )]))
+        .unwrap();
+
+        assert_eq!(metadata.mod_id.as_deref(), Some("quilted_fabric_api"));
+        assert_eq!(metadata.name.as_deref(), Some("Quilted Fabric API"));
+        assert_eq!(metadata.version.as_deref(), Some("7.4.0+0.90.0-1.20.1"));
+        assert_eq!(metadata.side, Some(SideType::Both));
+        assert_eq!(
+            metadata.dependencies,
+            vec![
+                Dependency {
+                    mod_id: "quilt_loader".to_string(),
+                    version: VersionReq::Predicates(vec![">=0.19.2".to_string()]),
+                },
+                Dependency {
+                    mod_id: "minecraft".to_string(),
+                    version: VersionReq::Any,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_neoforge_mods_toml() {
+        let metadata = ModMetadata::extract_from_reader(jar(&[(
+            "META-INF/neoforge.mods.toml",
+            include_str!("fixtures/neoforge.mods.toml"),
+        )]))
+        .unwrap();
+
+        assert_eq!(metadata.mod_id.as_deref(), Some("sodium"));
+        assert_eq!(metadata.name.as_deref(), Some("Sodium"));
+        assert_eq!(metadata.version.as_deref(), Some("0.6.0-beta.2+mc1.21.1"));
+        assert_eq!(metadata.side, Some(SideType::Client));
+        assert!(metadata.dependencies.is_empty());
+    }
+
+    #[test]
+    fn reads_fabric_depends_and_provides() {
+        let metadata = ModMetadata::extract_from_reader(jar(&[(
+            "fabric.mod.json",
+            r#"{
+                "id": "fabric-api",
+                "provides": ["fabric"],
+                "depends": {"fabricloader": ">=0.14.22", "minecraft": ["1.20", "1.20.1"], "cloth-config": "*"}
+            }"#,
+        )]))
+        .unwrap();
+
+        assert_eq!(metadata.provides, vec!["fabric".to_string()]);
+        let minecraft = metadata
+            .dependencies
+            .iter()
+            .find(|dependency| dependency.mod_id == "minecraft")
+            .unwrap();
+        assert_eq!(
+            minecraft.version,
+            VersionReq::Predicates(vec!["1.20".to_string(), "1.20.1".to_string()])
+        );
+        assert!(
+            metadata
+                .dependencies
+                .iter()
+                .any(|dependency| dependency.mod_id == "cloth-config"
+                    && dependency.version == VersionReq::Any)
+        );
+    }
+
+    #[test]
+    fn reads_environment_from_fabric_mod_json() {
+        let client = jar(&[(
+            "fabric.mod.json",
+            r#"{"id": "sodium", "version": "0.5.0", "environment": "client"}"#,
+        )]);
+        let both = jar(&[(
+            "fabric.mod.json",
+            r#"{"id": "lithium", "environment": "*"}"#,
+        )]);
+        let unspecified = jar(&[("fabric.mod.json", r#"{"id": "carpet"}"#)]);
+
+        assert_eq!(
+            ModMetadata::extract_from_reader(client).unwrap().side,
+            Some(SideType::Client)
+        );
+        assert_eq!(
+            ModMetadata::extract_from_reader(both).unwrap().side,
+            Some(SideType::Both)
+        );
+        assert_eq!(
+            ModMetadata::extract_from_reader(unspecified).unwrap().side,
+            None
+        );
+    }
+
+    #[test]
+    fn reads_side_hints_from_mods_toml() {
+        let client_dependency = jar(&[(
+            "META-INF/mods.toml",
+            r#"
+modLoader = "javafml"
+
+[[mods]]
+modId = "oculus"
+
+[[dependencies.oculus]]
+modId = "forge"
+mandatory = true
+side = "BOTH"
+
+[[dependencies.oculus]]
+modId = "minecraft"
+mandatory = true
+side = "CLIENT"
+"#,
+        )]);
+        let display_test = jar(&[(
+            "META-INF/mods.toml",
+            r#"
+[[mods]]
+modId = "jeed"
+displayTest = "IGNORE_ALL_VERSION"
+"#,
+        )]);
+        let server_only = jar(&[(
+            "META-INF/mods.toml",
+            r#"
+[[mods]]
+modId = "spark"
+displayTest = "IGNORE_SERVER_VERSION"
+"#,
+        )]);
+        let declared_over_display_test = jar(&[(
+            "META-INF/mods.toml",
+            r#"
+[[mods]]
+modId = "ftblibrary"
+displayTest = "IGNORE_ALL_VERSION"
+
+[[dependencies.ftblibrary]]
+modId = "minecraft"
+side = "BOTH"
+"#,
+        )]);
+        let both = jar(&[(
+            "META-INF/mods.toml",
+            r#"
+[[mods]]
+modId = "create"
+
+[[dependencies.create]]
+modId = "minecraft"
+side = "BOTH"
+"#,
+        )]);
+
+        assert_eq!(
+            ModMetadata::extract_from_reader(client_dependency)
+                .unwrap()
+                .side,
+            Some(SideType::Client)
+        );
+        assert_eq!(
+            ModMetadata::extract_from_reader(display_test).unwrap().side,
+            None
+        );
+        assert_eq!(
+            ModMetadata::extract_from_reader(server_only).unwrap().side,
+            Some(SideType::Server)
+        );
+        assert_eq!(
+            ModMetadata::extract_from_reader(declared_over_display_test)
+                .unwrap()
+                .side,
+            Some(SideType::Both)
+        );
+        assert_eq!(
+            ModMetadata::extract_from_reader(both).unwrap().side,
+            Some(SideType::Both)
+        );
+    }
+}
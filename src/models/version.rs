@@ -0,0 +1,253 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionReq {
+    Any,
+    /// Fabric/Quilt predicates; the dependency is satisfied when any of them matches.
+    Predicates(Vec<String>),
+    /// Maven version range as used by Forge and NeoForge `versionRange`.
+    MavenRange(String),
+}
+
+impl VersionReq {
+    pub fn matches(&self, version: &str) -> bool {
+        // Unresolved placeholders such as `${file.jarVersion}` cannot be compared.
+        if !version
+            .trim_start_matches('v')
+            .starts_with(|c: char| c.is_ascii_digit())
+        {
+            return true;
+        }
+
+        match self {
+            VersionReq::Any => true,
+            VersionReq::Predicates(predicates) => predicates
+                .iter()
+                .any(|predicate| matches_predicate(predicate, version)),
+            VersionReq::MavenRange(range) => matches_maven_range(range, version),
+        }
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VersionReq::Any => write!(f, "*"),
+            VersionReq::Predicates(predicates) => write!(f, "{}", predicates.join(" || ")),
+            VersionReq::MavenRange(range) => write!(f, "{}", range),
+        }
+    }
+}
+
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a_core, a_pre) = split_version(a);
+    let (b_core, b_pre) = split_version(b);
+
+    compare_parts(a_core, b_core).then_with(|| match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => compare_parts(a, b),
+    })
+}
+
+fn split_version(version: &str) -> (&str, Option<&str>) {
+    let version = version.trim().trim_start_matches('v');
+    let version = version.split_once('+').map_or(version, |(core, _)| core);
+    match version.split_once('-') {
+        Some((core, pre)) => (core, Some(pre)),
+        None => (version, None),
+    }
+}
+
+fn compare_parts(a: &str, b: &str) -> Ordering {
+    let mut a_parts = a.split('.');
+    let mut b_parts = b.split('.');
+
+    loop {
+        let ordering = match (a_parts.next(), b_parts.next()) {
+            (None, None) => return Ordering::Equal,
+            (Some(a), None) => compare_part(a, "0"),
+            (None, Some(b)) => compare_part("0", b),
+            (Some(a), Some(b)) => compare_part(a, b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn compare_part(a: &str, b: &str) -> Ordering {
+    match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => Ordering::Greater,
+        (Err(_), Ok(_)) => Ordering::Less,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+fn matches_predicate(predicate: &str, version: &str) -> bool {
+    predicate
+        .split_whitespace()
+        .all(|term| matches_term(term, version))
+}
+
+fn matches_term(term: &str, version: &str) -> bool {
+    if term == "*" {
+        return true;
+    }
+
+    let operators = [">=", "<=", ">", "<", "=", "~", "^"];
+    let (operator, target) = operators
+        .iter()
+        .find_map(|op| term.strip_prefix(op).map(|rest| (*op, rest)))
+        .unwrap_or(("=", term));
+
+    match operator {
+        ">=" => compare_versions(version, target).is_ge(),
+        "<=" => compare_versions(version, target).is_le(),
+        ">" => compare_versions(version, target).is_gt(),
+        "<" => compare_versions(version, target).is_lt(),
+        "~" => {
+            compare_versions(version, target).is_ge()
+                && compare_versions(version, &bump(target, 1)).is_lt()
+        }
+        "^" => {
+            compare_versions(version, target).is_ge()
+                && compare_versions(version, &bump(target, 0)).is_lt()
+        }
+        _ => matches_exact(target, version),
+    }
+}
+
+/// Returns the smallest version above every version sharing `target`'s first `index + 1` parts.
+fn bump(target: &str, index: usize) -> String {
+    let (core, _) = split_version(target);
+    let mut parts: Vec<u64> = core
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect();
+    let index = index.min(parts.len() - 1);
+    parts.truncate(index + 1);
+    parts[index] += 1;
+    parts
+        .iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn matches_exact(target: &str, version: &str) -> bool {
+    let (target_core, target_pre) = split_version(target);
+    if target_core
+        .split('.')
+        .any(|part| part == "x" || part == "X" || part == "*")
+    {
+        let (version_core, _) = split_version(version);
+        let mut version_parts = version_core.split('.');
+        return target_core.split('.').all(|part| {
+            let actual = version_parts.next().unwrap_or("0");
+            matches!(part, "x" | "X" | "*") || compare_part(part, actual).is_eq()
+        });
+    }
+
+    let (version_core, version_pre) = split_version(version);
+    compare_parts(target_core, version_core).is_eq()
+        && target_pre.is_none_or(|pre| version_pre == Some(pre))
+}
+
+fn matches_maven_range(range: &str, version: &str) -> bool {
+    let range = range.trim();
+    if !range.starts_with(['[', '(']) {
+        return true;
+    }
+
+    let mut rest = range;
+    while let Some(start) = rest.find(['[', '(']) {
+        let Some(end) = rest[start..].find([']', ')']).map(|end| start + end) else {
+            return true;
+        };
+        if matches_maven_set(&rest[start..=end], version) {
+            return true;
+        }
+        rest = &rest[end + 1..];
+    }
+    false
+}
+
+fn matches_maven_set(set: &str, version: &str) -> bool {
+    let lower_inclusive = set.starts_with('[');
+    let upper_inclusive = set.ends_with(']');
+    let inner = &set[1..set.len() - 1];
+
+    let Some((lower, upper)) = inner.split_once(',') else {
+        return compare_versions(version, inner.trim()).is_eq();
+    };
+    let (lower, upper) = (lower.trim(), upper.trim());
+
+    let above_lower = lower.is_empty() || {
+        let ordering = compare_versions(version, lower);
+        ordering.is_gt() || (lower_inclusive && ordering.is_eq())
+    };
+    let below_upper = upper.is_empty() || {
+        let ordering = compare_versions(version, upper);
+        ordering.is_lt() || (upper_inclusive && ordering.is_eq())
+    };
+    above_lower && below_upper
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VersionReq, compare_versions};
+    use std::cmp::Ordering;
+
+    fn predicates(predicates: &[&str]) -> VersionReq {
+        VersionReq::Predicates(predicates.iter().map(|p| p.to_string()).collect())
+    }
+
+    #[test]
+    fn orders_versions_numerically_with_prereleases_first() {
+        assert_eq!(compare_versions("1.20.10", "1.20.2"), Ordering::Greater);
+        assert_eq!(compare_versions("1.20", "1.20.0"), Ordering::Equal);
+        assert_eq!(compare_versions("0.5.0-beta.2", "0.5.0"), Ordering::Less);
+        assert_eq!(
+            compare_versions("0.15.3+build.1", "0.15.3"),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn matches_fabric_predicates() {
+        assert!(predicates(&[">=0.14.21"]).matches("0.15.3"));
+        assert!(!predicates(&[">=0.14.21"]).matches("0.14.9"));
+        assert!(predicates(&["~1.20"]).matches("1.20.4"));
+        assert!(!predicates(&["~1.20"]).matches("1.21"));
+        assert!(predicates(&["^2.1"]).matches("2.9.0"));
+        assert!(!predicates(&["^2.1"]).matches("3.0.0"));
+        assert!(predicates(&["1.20.x"]).matches("1.20.1"));
+        assert!(predicates(&[">=1.20 <1.21"]).matches("1.20.6"));
+        assert!(predicates(&["1.19.2", "1.20.1"]).matches("1.20.1"));
+        assert!(!predicates(&["1.19.2", "1.20.1"]).matches("1.20.2"));
+        assert!(predicates(&["*"]).matches("9.9"));
+    }
+
+    #[test]
+    fn matches_maven_ranges() {
+        let range = |range: &str| VersionReq::MavenRange(range.to_string());
+
+        assert!(range("[47,)").matches("47.2.0"));
+        assert!(!range("[47,)").matches("46.0.1"));
+        assert!(range("[1.20.1,1.21)").matches("1.20.4"));
+        assert!(!range("[1.20.1,1.21)").matches("1.21"));
+        assert!(range("[1.20.1]").matches("1.20.1"));
+        assert!(range("(,1.0],[1.2,)").matches("1.3"));
+        assert!(!range("(,1.0],[1.2,)").matches("1.1"));
+        assert!(range("1.0").matches("0.1"));
+    }
+
+    #[test]
+    fn accepts_unresolved_version_placeholders() {
+        assert!(VersionReq::MavenRange("[2,)".to_string()).matches("${file.jarVersion}"));
+    }
+}
@@ -0,0 +1,572 @@
+use crate::models::ModInfo;
+use crate::ops::mod_cache::{self, SourceEntry};
+use crate::parsers::packwiz::is_packwiz_pack;
+use crate::utils::{cache_dir, collect_files, is_safe_relative_path};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+
+const MANIFEST_NAME: &str = "manifest.json";
+const FORMAT_VERSION: u32 = 1;
+/// Where imported packs are unpacked, inside the cache directory.
+const PACKS_DIR: &str = "bundle-packs";
+
+/// Lists every file in a bundle with its SHA-512, so an import can verify all of them.
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    format: u32,
+    /// The pack the bundle was made for.
+    pack: BundledPack,
+    /// Mod jars, stored under their SHA-512 and added to the mod cache on import.
+    mods: Vec<BundledFile>,
+    /// Loader files, client-only lists and CurseForge file names, relative to the cache directory.
+    cache_files: Vec<BundledFile>,
+    /// Which file each unhashed download URL produced.
+    sources: Vec<BundledSource>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundledPack {
+    /// The input to convert, relative to `pack/`: the pack file, its directory or its `pack.toml`.
+    input: String,
+    /// Every file of the pack, relative to `pack/`; directories and packwiz packs span several.
+    files: Vec<BundledFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct BundledFile {
+    path: String,
+    sha512: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct BundledSource {
+    url: String,
+    #[serde(flatten)]
+    entry: SourceEntry,
+}
+
+/// Files taken out of a bundle after their hashes matched the manifest.
+struct Unpacked {
+    pack_input: PathBuf,
+    pack: Vec<(PathBuf, PathBuf)>,
+    mods: Vec<PathBuf>,
+    cache_files: Vec<(PathBuf, PathBuf)>,
+    sources: Vec<BundledSource>,
+}
+
+enum Destination {
+    Pack(PathBuf),
+    Mod,
+    CacheFile(PathBuf),
+}
+
+/// What an import added to the local cache.
+pub struct Imported {
+    pub files: usize,
+    /// The bundled pack, unpacked into the cache directory.
+    pub pack: PathBuf,
+}
+
+/// Writes `pack`, the cached copy of every mod in `mods` and `cache_files` into a tar bundle.
+/// Every file must already be cached, which `mcpacker prefetch` takes care of.
+pub fn write(
+    output: &Path,
+    pack: &Path,
+    mods: &[ModInfo],
+    cache_files: &[PathBuf],
+) -> Result<usize> {
+    let mut mod_files = Vec::new();
+    let mut sources = Vec::new();
+    let mut missing = Vec::new();
+
+    for mod_info in mods {
+        if mod_info
+            .download_urls
+            .iter()
+            .all(|url| url.starts_with("file://"))
+        {
+            // Local files ship inside the pack itself.
+            continue;
+        }
+
+        if !mod_info.hash.is_empty() {
+            match mod_cache::cached_file(&mod_info.hash_algo, &mod_info.hash) {
+                Some(path) => mod_files.push(path),
+                None => missing.push(mod_info.name.clone()),
+            }
+            continue;
+        }
+
+        let url = &mod_info.download_urls[0];
+        let Some(entry) = mod_cache::lookup_source(url) else {
+            missing.push(format!("{} ({})", mod_info.name, url));
+            continue;
+        };
+        if let Some(sha512) = &entry.sha512 {
+            match mod_cache::cached_file("sha512", sha512) {
+                Some(path) => mod_files.push(path),
+                None => {
+                    missing.push(format!("{} ({})", mod_info.name, url));
+                    continue;
+                }
+            }
+        }
+        sources.push(BundledSource {
+            url: url.clone(),
+            entry,
+        });
+    }
+
+    if !missing.is_empty() {
+        bail!(
+            "{} files are missing from the mod cache:\n{}",
+            missing.len(),
+            missing
+                .iter()
+                .map(|item| format!("  - {}", item))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    write_archive(
+        output,
+        &cache_dir()?,
+        pack,
+        &mod_files,
+        cache_files,
+        sources,
+    )
+}
+
+/// Verifies every file in `bundle` and adds it to the local cache, so an `--offline` run finds it.
+/// When `pack` is a file, it must be the pack the bundle was made for.
+pub fn import(bundle: &Path, pack: Option<&Path>) -> Result<Imported> {
+    let cache_root = cache_dir()?;
+    let staging = tempfile::Builder::new()
+        .prefix("bundle-import-")
+        .tempdir_in(&cache_root)
+        .with_context(|| format!("Failed to create a staging directory in {:?}", cache_root))?;
+
+    let unpacked = unpack(bundle, pack, staging.path())?;
+    let files = unpacked.pack.len() + unpacked.mods.len() + unpacked.cache_files.len();
+    let packs_root = cache_root.join(PACKS_DIR);
+    // A pack imported earlier under the same name is replaced, so no stale files are left in it.
+    if let Some(Component::Normal(top)) = unpacked.pack_input.components().next() {
+        let previous = packs_root.join(top);
+        if previous.is_dir() {
+            std::fs::remove_dir_all(&previous)
+                .with_context(|| format!("Failed to remove {:?}", previous))?;
+        }
+    }
+    for (relative, path) in &unpacked.pack {
+        move_into(path, &packs_root.join(relative))?;
+    }
+    for path in &unpacked.mods {
+        mod_cache::store(path)?;
+    }
+    for (relative, path) in &unpacked.cache_files {
+        move_into(path, &cache_root.join(relative))?;
+    }
+    for source in &unpacked.sources {
+        mod_cache::record_source(&source.url, &source.entry)?;
+    }
+
+    Ok(Imported {
+        files,
+        pack: packs_root.join(&unpacked.pack_input),
+    })
+}
+
+fn move_into(path: &Path, target: &Path) -> Result<()> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::rename(path, target).with_context(|| format!("Failed to import {:?}", target))
+}
+
+/// Returns the input path under `pack/` and every file to bundle, keyed by its path under `pack/`.
+/// Directories and packwiz packs are bundled whole, below a folder named after the directory.
+fn pack_files(pack: &Path) -> Result<(String, Vec<(String, PathBuf)>)> {
+    let pack = pack
+        .canonicalize()
+        .with_context(|| format!("Failed to read pack: {:?}", pack))?;
+    let name_of = |path: &Path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .with_context(|| format!("{:?} has no file name", path))
+    };
+
+    if pack.is_file() && !is_packwiz_pack(&pack) {
+        let name = name_of(&pack)?;
+        return Ok((name.clone(), vec![(name, pack)]));
+    }
+
+    let (root, input) = if pack.is_dir() {
+        (pack.as_path(), name_of(&pack)?)
+    } else {
+        let root = pack.parent().context("Pack path has no parent directory")?;
+        (root, format!("{}/{}", name_of(root)?, name_of(&pack)?))
+    };
+    let root_name = name_of(root)?;
+
+    let mut paths = Vec::new();
+    collect_files(root, &mut paths);
+    let mut files = Vec::new();
+    for path in paths {
+        let relative = path.strip_prefix(root)?;
+        if relative.components().any(|c| c.as_os_str() == ".git") {
+            continue;
+        }
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        files.push((format!("{}/{}", root_name, relative), path));
+    }
+    Ok((input, files))
+}
+
+fn write_archive(
+    output: &Path,
+    cache_root: &Path,
+    pack: &Path,
+    mod_files: &[PathBuf],
+    cache_files: &[PathBuf],
+    sources: Vec<BundledSource>,
+) -> Result<usize> {
+    // Mods shared by several pack entries are stored once.
+    let mut entries = BTreeMap::new();
+    let (input, files) = pack_files(pack)?;
+    let mut manifest = Manifest {
+        format: FORMAT_VERSION,
+        pack: BundledPack {
+            input,
+            files: Vec::new(),
+        },
+        mods: Vec::new(),
+        cache_files: Vec::new(),
+        sources,
+    };
+
+    for (relative, path) in files {
+        let file = BundledFile {
+            sha512: mod_cache::file_sha512(&path)?,
+            path: relative,
+        };
+        entries.insert(format!("pack/{}", file.path), path);
+        manifest.pack.files.push(file);
+    }
+
+    for path in mod_files {
+        let sha512 = mod_cache::file_sha512(path)?;
+        let file = BundledFile {
+            path: format!("mods/{}", sha512),
+            sha512,
+        };
+        if entries.insert(file.path.clone(), path.clone()).is_none() {
+            manifest.mods.push(file);
+        }
+    }
+
+    for path in cache_files {
+        let relative = path
+            .strip_prefix(cache_root)
+            .with_context(|| format!("{:?} is not inside the cache directory", path))?;
+        let file = BundledFile {
+            path: relative.to_string_lossy().replace('\\', "/"),
+            sha512: mod_cache::file_sha512(path)?,
+        };
+        entries.insert(format!("cache/{}", file.path), path.clone());
+        manifest.cache_files.push(file);
+    }
+
+    let temp_output = output.with_file_name(format!(
+        "{}.part",
+        output.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let file = File::create(&temp_output)
+        .with_context(|| format!("Failed to create bundle: {:?}", temp_output))?;
+    let mut builder = tar::Builder::new(BufWriter::new(file));
+
+    // The manifest goes first so an import knows every hash before it reads any file.
+    let manifest_bytes = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_bytes.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, MANIFEST_NAME, manifest_bytes.as_slice())?;
+
+    for (name, path) in &entries {
+        builder
+            .append_path_with_name(path, name)
+            .with_context(|| format!("Failed to add {:?} to the bundle", path))?;
+    }
+    builder.into_inner()?.flush()?;
+
+    std::fs::rename(&temp_output, output)
+        .with_context(|| format!("Failed to write bundle: {:?}", output))?;
+    Ok(entries.len())
+}
+
+fn unpack(bundle: &Path, pack: Option<&Path>, staging: &Path) -> Result<Unpacked> {
+    let file =
+        File::open(bundle).with_context(|| format!("Failed to open bundle: {:?}", bundle))?;
+    let mut archive = tar::Archive::new(BufReader::new(file));
+    let mut entries = archive.entries()?;
+
+    let first = entries.next().context("Bundle is empty")??;
+    if first.path()? != Path::new(MANIFEST_NAME) {
+        bail!(
+            "Not an mcpacker bundle: {:?} does not start with {}",
+            bundle,
+            MANIFEST_NAME
+        );
+    }
+    let manifest: Manifest =
+        serde_json::from_reader(first).context("Failed to parse bundle manifest")?;
+    if manifest.format != FORMAT_VERSION {
+        bail!(
+            "Unsupported bundle format {} (expected {})",
+            manifest.format,
+            FORMAT_VERSION
+        );
+    }
+
+    if let (Some(pack), [bundled]) = (pack, manifest.pack.files.as_slice())
+        && pack.is_file()
+        && mod_cache::file_sha512(pack)? != bundled.sha512
+    {
+        bail!(
+            "{:?} is not the pack this bundle was made for ({})",
+            pack,
+            bundled.path
+        );
+    }
+
+    let pack_input = PathBuf::from(&manifest.pack.input);
+    if !is_safe_relative_path(&pack_input) {
+        bail!("Unsafe path in bundle manifest: {}", manifest.pack.input);
+    }
+    let mut expected = HashMap::new();
+    for file in &manifest.pack.files {
+        let relative = PathBuf::from(&file.path);
+        if !is_safe_relative_path(&relative) {
+            bail!("Unsafe path in bundle manifest: {}", file.path);
+        }
+        expected.insert(
+            format!("pack/{}", file.path),
+            (Destination::Pack(relative), &file.sha512),
+        );
+    }
+    for file in &manifest.mods {
+        expected.insert(file.path.clone(), (Destination::Mod, &file.sha512));
+    }
+    for file in &manifest.cache_files {
+        let relative = PathBuf::from(&file.path);
+        if !is_safe_relative_path(&relative) {
+            bail!("Unsafe path in bundle manifest: {}", file.path);
+        }
+        expected.insert(
+            format!("cache/{}", file.path),
+            (Destination::CacheFile(relative), &file.sha512),
+        );
+    }
+
+    let mut unpacked = Unpacked {
+        pack_input,
+        pack: Vec::new(),
+        mods: Vec::new(),
+        cache_files: Vec::new(),
+        sources: manifest.sources.clone(),
+    };
+    for (index, entry) in entries.enumerate() {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let Some((destination, sha512)) = expected.remove(&name) else {
+            bail!(
+                "Bundle contains a file that is not in its manifest: {}",
+                name
+            );
+        };
+
+        let path = staging.join(index.to_string());
+        std::io::copy(&mut entry, &mut File::create(&path)?)
+            .with_context(|| format!("Failed to read {} from the bundle", name))?;
+        let computed = mod_cache::file_sha512(&path)?;
+        if !computed.eq_ignore_ascii_case(sha512) {
+            bail!(
+                "Hash mismatch for {} in bundle: expected {}, got {}",
+                name,
+                sha512,
+                computed
+            );
+        }
+
+        match destination {
+            Destination::Pack(relative) => unpacked.pack.push((relative, path)),
+            Destination::Mod => unpacked.mods.push(path),
+            Destination::CacheFile(relative) => unpacked.cache_files.push((relative, path)),
+        }
+    }
+
+    if !expected.is_empty() {
+        let mut missing = expected.into_keys().collect::<Vec<_>>();
+        missing.sort();
+        bail!(
+            "Bundle is missing {} files listed in its manifest:\n  - {}",
+            missing.len(),
+            missing.join("\n  - ")
+        );
+    }
+
+    Ok(unpacked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BundledSource, unpack, write_archive};
+    use crate::ops::mod_cache::SourceEntry;
+    use crate::test_support::temp_dir;
+    use std::path::{Path, PathBuf};
+    use tempfile::TempDir;
+
+    fn setup() -> TempDir {
+        let root = temp_dir();
+        let cache = root.path().join("cache");
+        std::fs::create_dir_all(cache.join("filter_lists")).unwrap();
+        std::fs::create_dir_all(root.path().join("staging")).unwrap();
+        std::fs::write(root.path().join("pack.mrpack"), b"pack").unwrap();
+        std::fs::write(root.path().join("mod.jar"), b"mod contents").unwrap();
+        std::fs::write(cache.join("filter_lists/default.txt"), b"keyword-list").unwrap();
+        root
+    }
+
+    fn write_bundle(root: &Path) -> PathBuf {
+        let cache = root.join("cache");
+        let bundle = root.join("bundle.tar");
+        let source = BundledSource {
+            url: "https://example.com/mod.jar".to_string(),
+            entry: SourceEntry {
+                file_name: "mod.jar".to_string(),
+                sha512: None,
+            },
+        };
+        let written = write_archive(
+            &bundle,
+            &cache,
+            &root.join("pack.mrpack"),
+            &[root.join("mod.jar"), root.join("mod.jar")],
+            &[cache.join("filter_lists/default.txt")],
+            vec![source],
+        )
+        .unwrap();
+        assert_eq!(written, 3);
+        bundle
+    }
+
+    #[test]
+    fn round_trips_files_and_sources() {
+        let root = setup();
+        let root = root.path();
+        let bundle = write_bundle(root);
+
+        let unpacked = unpack(
+            &bundle,
+            Some(&root.join("pack.mrpack")),
+            &root.join("staging"),
+        )
+        .unwrap();
+
+        assert_eq!(unpacked.pack_input, PathBuf::from("pack.mrpack"));
+        assert_eq!(unpacked.pack[0].0, PathBuf::from("pack.mrpack"));
+        assert_eq!(std::fs::read(&unpacked.pack[0].1).unwrap(), b"pack");
+        assert_eq!(unpacked.mods.len(), 1);
+        assert_eq!(std::fs::read(&unpacked.mods[0]).unwrap(), b"mod contents");
+        assert_eq!(
+            unpacked.cache_files[0].0,
+            PathBuf::from("filter_lists/default.txt")
+        );
+        assert_eq!(
+            std::fs::read(&unpacked.cache_files[0].1).unwrap(),
+            b"keyword-list"
+        );
+        assert_eq!(unpacked.sources[0].url, "https://example.com/mod.jar");
+    }
+
+    #[test]
+    fn bundles_every_file_of_a_packwiz_pack() {
+        let root = temp_dir();
+        let pack_dir = root.path().join("my-pack");
+        std::fs::create_dir_all(pack_dir.join("mods")).unwrap();
+        std::fs::create_dir_all(pack_dir.join(".git")).unwrap();
+        std::fs::write(pack_dir.join("pack.toml"), b"name = \"My Pack\"").unwrap();
+        std::fs::write(pack_dir.join("mods/sodium.pw.toml"), b"name = \"Sodium\"").unwrap();
+        std::fs::write(pack_dir.join(".git/HEAD"), b"ref: refs/heads/main").unwrap();
+        let bundle = root.path().join("bundle.tar");
+        write_archive(
+            &bundle,
+            root.path(),
+            &pack_dir.join("pack.toml"),
+            &[],
+            &[],
+            Vec::new(),
+        )
+        .unwrap();
+
+        let staging = root.path().join("staging");
+        std::fs::create_dir_all(&staging).unwrap();
+        let unpacked = unpack(&bundle, None, &staging).unwrap();
+
+        assert_eq!(unpacked.pack_input, PathBuf::from("my-pack/pack.toml"));
+        let mut files: Vec<_> = unpacked
+            .pack
+            .iter()
+            .map(|(relative, path)| (relative.clone(), std::fs::read(path).unwrap()))
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                (
+                    PathBuf::from("my-pack/mods/sodium.pw.toml"),
+                    b"name = \"Sodium\"".to_vec()
+                ),
+                (
+                    PathBuf::from("my-pack/pack.toml"),
+                    b"name = \"My Pack\"".to_vec()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_tampered_files_and_other_packs() {
+        let root = setup();
+        let root = root.path();
+        let bundle = write_bundle(root);
+
+        std::fs::write(root.join("other.mrpack"), b"other").unwrap();
+        let error = unpack(
+            &bundle,
+            Some(&root.join("other.mrpack")),
+            &root.join("staging"),
+        )
+        .err()
+        .unwrap();
+        assert!(error.to_string().contains("not the pack"), "{:#}", error);
+
+        let bytes = std::fs::read(&bundle).unwrap();
+        let at = bytes
+            .windows(12)
+            .position(|window| window == b"keyword-list")
+            .unwrap();
+        let mut tampered = bytes.clone();
+        tampered[at] = b'K';
+        std::fs::write(&bundle, tampered).unwrap();
+
+        let error = unpack(&bundle, None, &root.join("staging")).err().unwrap();
+        assert!(error.to_string().contains("Hash mismatch"), "{:#}", error);
+    }
+}
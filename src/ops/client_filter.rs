@@ -0,0 +1,413 @@
+use crate::models::{ModMetadata, SideType};
+use crate::parsers::filter::{Keyword, KeywordMatcher};
+use crate::parsers::rules::{FilterRules, RuleAction, RuleTarget};
+use crate::resolvers;
+use crate::retry::RetryPolicy;
+use anyhow::{Context, Result};
+use sha1::Sha1;
+use sha2::{Digest, Sha512};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+pub const CLIENT_MODS_DIR: &str = "client-mods";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientOnlyReason {
+    Rules,
+    Modrinth,
+    Metadata,
+    Keyword(Keyword),
+}
+
+impl fmt::Display for ClientOnlyReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientOnlyReason::Rules => write!(f, "rules file"),
+            ClientOnlyReason::Modrinth => write!(f, "Modrinth server_side"),
+            ClientOnlyReason::Metadata => write!(f, "jar metadata"),
+            ClientOnlyReason::Keyword(keyword) => write!(
+                f,
+                "keyword \"{}\" from {}",
+                keyword.value,
+                keyword.sources.join(", ")
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MovedJar {
+    pub file_name: String,
+    pub reason: ClientOnlyReason,
+}
+
+pub struct ClientFilter {
+    /// `None` without `--filter-client`, where only the rules decide.
+    pub keywords: Option<KeywordMatcher>,
+    pub rules: FilterRules,
+    /// Jars in `mods/` whose pack entry `force_keep` matched before download.
+    pub pinned: HashSet<String>,
+    /// Sides reported by Modrinth, keyed by jar file name.
+    pub modrinth_sides: HashMap<String, SideType>,
+}
+
+/// Looks up every jar in `mods/` on Modrinth by SHA-1 and maps its project's `server_side`.
+pub async fn modrinth_sides(
+    output_dir: &Path,
+    api_base: &str,
+    proxy: Option<&str>,
+    retry: RetryPolicy,
+) -> Result<HashMap<String, SideType>> {
+    let mods_dir = output_dir.join("mods");
+    let jars = tokio::task::spawn_blocking(move || {
+        let Ok(entries) = std::fs::read_dir(&mods_dir) else {
+            return Ok(Vec::new());
+        };
+        entries
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "jar"))
+            .map(|entry| {
+                let sha1 = jar_sha1(&entry.path())?;
+                Ok((entry.file_name().to_string_lossy().into_owned(), sha1))
+            })
+            .collect::<Result<Vec<_>>>()
+    })
+    .await??;
+
+    let hashes: Vec<String> = jars.iter().map(|(_, sha1)| sha1.clone()).collect();
+    let server_sides =
+        resolvers::modrinth::lookup_server_sides(api_base, &hashes, proxy, retry).await?;
+
+    Ok(jars
+        .into_iter()
+        .filter_map(|(file_name, sha1)| {
+            side_from_server_side(server_sides.get(&sha1)?).map(|side| (file_name, side))
+        })
+        .collect())
+}
+
+fn side_from_server_side(server_side: &str) -> Option<SideType> {
+    match server_side {
+        "unsupported" => Some(SideType::Client),
+        "required" | "optional" => Some(SideType::Both),
+        _ => None,
+    }
+}
+
+pub async fn move_client_only_jars(
+    output_dir: &Path,
+    filter: ClientFilter,
+) -> Result<Vec<MovedJar>> {
+    let mods_dir = output_dir.join("mods");
+    let client_dir = output_dir.join(CLIENT_MODS_DIR);
+
+    tokio::task::spawn_blocking(move || {
+        let mut moved = Vec::new();
+        let Ok(entries) = std::fs::read_dir(&mods_dir) else {
+            return Ok(moved);
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.is_file() || path.extension().is_none_or(|ext| ext != "jar") {
+                continue;
+            }
+
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let metadata = ModMetadata::extract_from_jar(&path).unwrap_or_default();
+            let hashes = if filter.rules.has_hash_rules() {
+                jar_hashes(&path)?.to_vec()
+            } else {
+                Vec::new()
+            };
+            let action = if filter.pinned.contains(&file_name) {
+                Some(RuleAction::Keep)
+            } else {
+                filter.rules.action(&RuleTarget {
+                    mod_id: metadata.mod_id.as_deref(),
+                    file_name: Some(&file_name),
+                    hashes: hashes.iter().map(String::as_str).collect(),
+                    ..RuleTarget::default()
+                })
+            };
+            let Some(reason) = client_only_reason(
+                &file_name,
+                &metadata,
+                filter.modrinth_sides.get(&file_name).cloned(),
+                filter.keywords.as_ref(),
+                action,
+            ) else {
+                continue;
+            };
+
+            std::fs::create_dir_all(&client_dir)?;
+            std::fs::rename(&path, client_dir.join(&file_name))
+                .with_context(|| format!("Failed to move client-only mod: {:?}", path))?;
+            moved.push(MovedJar { file_name, reason });
+        }
+
+        moved.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+        Ok::<_, anyhow::Error>(moved)
+    })
+    .await?
+}
+
+fn read_jar(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("Failed to read mod jar: {:?}", path))
+}
+
+fn jar_sha1(path: &Path) -> Result<String> {
+    Ok(hex::encode(Sha1::digest(read_jar(path)?)))
+}
+
+fn jar_hashes(path: &Path) -> Result<[String; 2]> {
+    let bytes = read_jar(path)?;
+    Ok([
+        hex::encode(Sha1::digest(&bytes)),
+        hex::encode(Sha512::digest(&bytes)),
+    ])
+}
+
+fn client_only_reason(
+    file_name: &str,
+    metadata: &ModMetadata,
+    modrinth_side: Option<SideType>,
+    keywords: Option<&KeywordMatcher>,
+    action: Option<RuleAction>,
+) -> Option<ClientOnlyReason> {
+    match action {
+        Some(RuleAction::Keep) => return None,
+        Some(RuleAction::Remove) => return Some(ClientOnlyReason::Rules),
+        None => {}
+    }
+    let keywords = keywords?;
+
+    match modrinth_side {
+        Some(SideType::Client) => return Some(ClientOnlyReason::Modrinth),
+        Some(_) => return None,
+        None => {}
+    }
+
+    match metadata.side {
+        Some(SideType::Client) => Some(ClientOnlyReason::Metadata),
+        Some(_) => None,
+        None => {
+            let keyword = keywords.find(file_name).or_else(|| {
+                metadata
+                    .mod_id
+                    .as_deref()
+                    .and_then(|mod_id| keywords.find(mod_id))
+            });
+            keyword.map(|keyword| ClientOnlyReason::Keyword(keyword.clone()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CLIENT_MODS_DIR, ClientFilter, ClientOnlyReason, client_only_reason, move_client_only_jars,
+        side_from_server_side,
+    };
+    use crate::models::{ModMetadata, SideType};
+    use crate::parsers::filter::{Keyword, KeywordMatch, KeywordMatcher};
+    use crate::parsers::rules::{FilterRules, RuleAction};
+    use crate::test_support::{temp_dir, write_zip};
+    use std::collections::{HashMap, HashSet};
+    use std::path::Path;
+
+    fn keyword() -> Keyword {
+        Keyword {
+            value: "sodium".to_string(),
+            sources: vec!["serverpackcreator".to_string()],
+        }
+    }
+
+    fn keywords() -> KeywordMatcher {
+        KeywordMatcher::new(vec![keyword()], KeywordMatch::Token).unwrap()
+    }
+
+    fn metadata(side: Option<SideType>) -> ModMetadata {
+        ModMetadata {
+            mod_id: Some("sodium".to_string()),
+            side,
+            ..ModMetadata::default()
+        }
+    }
+
+    #[test]
+    fn prefers_declared_side_over_keywords() {
+        let keywords = keywords();
+
+        assert_eq!(
+            client_only_reason(
+                "sodium.jar",
+                &metadata(Some(SideType::Client)),
+                None,
+                Some(&keywords),
+                None
+            ),
+            Some(ClientOnlyReason::Metadata)
+        );
+        assert_eq!(
+            client_only_reason(
+                "sodium.jar",
+                &metadata(Some(SideType::Both)),
+                None,
+                Some(&keywords),
+                None
+            ),
+            None
+        );
+        assert_eq!(
+            client_only_reason("sodium.jar", &metadata(None), None, Some(&keywords), None),
+            Some(ClientOnlyReason::Keyword(keyword()))
+        );
+        assert_eq!(
+            client_only_reason(
+                "lithium.jar",
+                &ModMetadata::default(),
+                None,
+                Some(&keywords),
+                None
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn rules_take_precedence_over_metadata_and_keywords() {
+        let keywords = keywords();
+
+        assert_eq!(
+            client_only_reason(
+                "sodium.jar",
+                &metadata(Some(SideType::Client)),
+                None,
+                Some(&keywords),
+                Some(RuleAction::Keep)
+            ),
+            None
+        );
+        assert_eq!(
+            client_only_reason(
+                "lithium.jar",
+                &ModMetadata::default(),
+                None,
+                Some(&keywords),
+                Some(RuleAction::Remove)
+            ),
+            Some(ClientOnlyReason::Rules)
+        );
+    }
+
+    #[test]
+    fn trusts_modrinth_server_side_over_metadata_and_keywords() {
+        let keywords = keywords();
+
+        assert_eq!(
+            client_only_reason(
+                "CF-1234.jar",
+                &metadata(Some(SideType::Both)),
+                side_from_server_side("unsupported"),
+                Some(&keywords),
+                None
+            ),
+            Some(ClientOnlyReason::Modrinth)
+        );
+        assert_eq!(
+            client_only_reason(
+                "sodium.jar",
+                &metadata(None),
+                side_from_server_side("optional"),
+                Some(&keywords),
+                None
+            ),
+            None
+        );
+        assert_eq!(side_from_server_side("unknown"), None);
+    }
+
+    fn write_jar(mods_dir: &Path, file_name: &str, mods_toml: &str) {
+        std::fs::create_dir_all(mods_dir).unwrap();
+        write_zip(
+            &mods_dir.join(file_name),
+            &[("META-INF/mods.toml", mods_toml)],
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_jars_that_only_set_ignore_all_version() {
+        let output_dir = temp_dir();
+        let mods_dir = output_dir.path().join("mods");
+        write_jar(
+            &mods_dir,
+            "appleskin.jar",
+            "[[mods]]\nmodId = \"appleskin\"\ndisplayTest = \"IGNORE_ALL_VERSION\"\n",
+        );
+
+        let moved = move_client_only_jars(
+            output_dir.path(),
+            ClientFilter {
+                keywords: Some(keywords()),
+                rules: FilterRules::default(),
+                pinned: HashSet::new(),
+                modrinth_sides: HashMap::new(),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(moved.is_empty());
+        assert!(mods_dir.join("appleskin.jar").is_file());
+        assert!(!output_dir.path().join(CLIENT_MODS_DIR).exists());
+    }
+
+    #[tokio::test]
+    async fn applies_only_the_rules_without_keywords() {
+        let output_dir = temp_dir();
+        let mods_dir = output_dir.path().join("mods");
+        let client_side = "[[mods]]\nmodId = \"sodium\"\n\n[[dependencies.sodium]]\nmodId = \"minecraft\"\nside = \"CLIENT\"\n";
+        write_jar(&mods_dir, "sodium.jar", client_side);
+        write_jar(
+            &mods_dir,
+            "journeymap.jar",
+            "[[mods]]\nmodId = \"journeymap\"\n",
+        );
+        write_jar(
+            &mods_dir,
+            "Renamed-1.0.jar",
+            "[[mods]]\nmodId = \"pinned\"\n",
+        );
+
+        let moved = move_client_only_jars(
+            output_dir.path(),
+            ClientFilter {
+                keywords: None,
+                rules: FilterRules::from_toml(&[
+                    "[force_remove]\nmod_ids = [\"journeymap\", \"pinned\"]\n",
+                ]),
+                pinned: HashSet::from(["Renamed-1.0.jar".to_string()]),
+                modrinth_sides: HashMap::new(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let moved: Vec<_> = moved
+            .iter()
+            .map(|jar| (jar.file_name.as_str(), &jar.reason))
+            .collect();
+        assert_eq!(moved, [("journeymap.jar", &ClientOnlyReason::Rules)]);
+        assert!(mods_dir.join("sodium.jar").is_file());
+        assert!(mods_dir.join("Renamed-1.0.jar").is_file());
+        assert!(
+            output_dir
+                .path()
+                .join(CLIENT_MODS_DIR)
+                .join("journeymap.jar")
+                .is_file()
+        );
+    }
+}
@@ -0,0 +1,356 @@
+use crate::models::{Dependency, ModMetadata};
+use crate::ops::client_filter::CLIENT_MODS_DIR;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+
+/// Dependencies provided by the game or the loader rather than by a jar in `mods/`.
+const PLATFORM_IDS: [&str; 9] = [
+    "minecraft",
+    "java",
+    "fabricloader",
+    "fabric-loader",
+    "quilt_loader",
+    "forge",
+    "neoforge",
+    "javafml",
+    "lowcodefml",
+];
+
+const MAX_NESTED_DEPTH: usize = 3;
+
+#[derive(Debug)]
+pub struct InstalledMod {
+    pub file_name: String,
+    pub metadata: ModMetadata,
+    pub nested: Vec<ModMetadata>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DependencyProblem {
+    Missing {
+        file_name: String,
+        dependency: Dependency,
+        in_client_mods: bool,
+    },
+    Incompatible {
+        file_name: String,
+        dependency: Dependency,
+        found: String,
+    },
+}
+
+impl fmt::Display for DependencyProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DependencyProblem::Missing {
+                file_name,
+                dependency,
+                in_client_mods,
+            } => {
+                write!(
+                    f,
+                    "{} requires {} {}, which is missing",
+                    file_name, dependency.mod_id, dependency.version
+                )?;
+                if *in_client_mods {
+                    write!(f, " (moved to {}/)", CLIENT_MODS_DIR)?;
+                }
+                Ok(())
+            }
+            DependencyProblem::Incompatible {
+                file_name,
+                dependency,
+                found,
+            } => write!(
+                f,
+                "{} requires {} {}, found {}",
+                file_name, dependency.mod_id, dependency.version, found
+            ),
+        }
+    }
+}
+
+pub async fn check_dependencies(output_dir: &Path) -> Result<Vec<DependencyProblem>> {
+    let mods_dir = output_dir.join("mods");
+    let client_dir = output_dir.join(CLIENT_MODS_DIR);
+
+    tokio::task::spawn_blocking(move || {
+        let installed = scan_jars(&mods_dir);
+        let client_only = scan_jars(&client_dir);
+        find_problems(&installed, &client_only)
+    })
+    .await
+    .map_err(Into::into)
+}
+
+pub fn scan_jars(dir: &Path) -> Vec<InstalledMod> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut mods: Vec<InstalledMod> = entries
+        .flatten()
+        .filter(|entry| {
+            let path = entry.path();
+            path.is_file() && path.extension().is_some_and(|ext| ext == "jar")
+        })
+        .filter_map(|entry| {
+            let file = std::fs::File::open(entry.path()).ok()?;
+            let mut nested = Vec::new();
+            let metadata = read_with_nested(file, 0, &mut nested)?;
+            Some(InstalledMod {
+                file_name: entry.file_name().to_string_lossy().into_owned(),
+                metadata,
+                nested,
+            })
+        })
+        .collect();
+
+    mods.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    mods
+}
+
+fn read_with_nested<R: Read + Seek>(
+    reader: R,
+    depth: usize,
+    nested: &mut Vec<ModMetadata>,
+) -> Option<ModMetadata> {
+    let mut archive = zip::ZipArchive::new(reader).ok()?;
+
+    if depth < MAX_NESTED_DEPTH {
+        let jar_names: Vec<String> = archive
+            .file_names()
+            .filter(|name| name.ends_with(".jar"))
+            .map(str::to_string)
+            .collect();
+
+        for name in jar_names {
+            let mut bytes = Vec::new();
+            let Ok(mut entry) = archive.by_name(&name) else {
+                continue;
+            };
+            if entry.read_to_end(&mut bytes).is_err() {
+                continue;
+            }
+            if let Some(metadata) = read_with_nested(Cursor::new(bytes), depth + 1, nested)
+                && metadata.mod_id.is_some()
+            {
+                nested.push(metadata);
+            }
+        }
+    }
+
+    let mut reader = archive.into_inner();
+    reader.rewind().ok()?;
+    ModMetadata::extract_from_reader(reader).ok()
+}
+
+fn provided_versions(mods: &[InstalledMod]) -> HashMap<&str, Vec<&str>> {
+    let mut provided: HashMap<&str, Vec<&str>> = HashMap::new();
+
+    for metadata in mods
+        .iter()
+        .flat_map(|installed| std::iter::once(&installed.metadata).chain(&installed.nested))
+    {
+        let version = metadata.version.as_deref().unwrap_or("");
+        for id in metadata.mod_id.iter().chain(&metadata.provides) {
+            provided.entry(id.as_str()).or_default().push(version);
+        }
+    }
+
+    provided
+}
+
+fn find_problems(
+    installed: &[InstalledMod],
+    client_only: &[InstalledMod],
+) -> Vec<DependencyProblem> {
+    let provided = provided_versions(installed);
+    let provided_by_client_mods = provided_versions(client_only);
+
+    let mut problems = Vec::new();
+    for installed_mod in installed {
+        let mut dependencies: Vec<&Dependency> = installed_mod
+            .metadata
+            .dependencies
+            .iter()
+            .filter(|dependency| !PLATFORM_IDS.contains(&dependency.mod_id.as_str()))
+            .collect();
+        dependencies.sort_by(|a, b| a.mod_id.cmp(&b.mod_id));
+
+        for dependency in dependencies {
+            match provided.get(dependency.mod_id.as_str()) {
+                None => problems.push(DependencyProblem::Missing {
+                    file_name: installed_mod.file_name.clone(),
+                    dependency: dependency.clone(),
+                    in_client_mods: provided_by_client_mods
+                        .contains_key(dependency.mod_id.as_str()),
+                }),
+                Some(versions)
+                    if !versions
+                        .iter()
+                        .any(|version| dependency.version.matches(version)) =>
+                {
+                    problems.push(DependencyProblem::Incompatible {
+                        file_name: installed_mod.file_name.clone(),
+                        dependency: dependency.clone(),
+                        found: versions.join(", "),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DependencyProblem, InstalledMod, find_problems, read_with_nested};
+    use crate::models::version::VersionReq;
+    use crate::models::{Dependency, ModMetadata};
+    use std::io::{Cursor, Write};
+
+    fn jar(
+        file_name: &str,
+        mod_id: &str,
+        version: &str,
+        dependencies: &[(&str, VersionReq)],
+    ) -> InstalledMod {
+        InstalledMod {
+            file_name: file_name.to_string(),
+            metadata: ModMetadata {
+                mod_id: Some(mod_id.to_string()),
+                version: Some(version.to_string()),
+                dependencies: dependencies
+                    .iter()
+                    .map(|(mod_id, version)| Dependency {
+                        mod_id: mod_id.to_string(),
+                        version: version.clone(),
+                    })
+                    .collect(),
+                ..ModMetadata::default()
+            },
+            nested: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reports_missing_and_incompatible_dependencies() {
+        let installed = vec![
+            jar(
+                "create.jar",
+                "create",
+                "0.5.1",
+                &[
+                    ("minecraft", VersionReq::MavenRange("[1.20.1]".to_string())),
+                    (
+                        "flywheel",
+                        VersionReq::MavenRange("[0.6.10,0.6.11)".to_string()),
+                    ),
+                    ("ponder", VersionReq::Any),
+                ],
+            ),
+            jar("flywheel.jar", "flywheel", "0.6.9", &[]),
+        ];
+        let client_only = vec![jar("ponder.jar", "ponder", "1.0", &[])];
+
+        let problems = find_problems(&installed, &client_only);
+
+        assert_eq!(problems.len(), 2);
+        assert!(matches!(
+            &problems[0],
+            DependencyProblem::Incompatible { found, .. } if found == "0.6.9"
+        ));
+        assert!(matches!(
+            &problems[1],
+            DependencyProblem::Missing {
+                in_client_mods: true,
+                ..
+            }
+        ));
+        assert_eq!(
+            problems[1].to_string(),
+            "create.jar requires ponder *, which is missing (moved to client-mods/)"
+        );
+    }
+
+    #[test]
+    fn accepts_dependencies_provided_by_nested_jars_and_aliases() {
+        let mut fabric_api = jar("fabric-api.jar", "fabric-api", "0.92.0", &[]);
+        fabric_api.metadata.provides = vec!["fabric".to_string()];
+        fabric_api.nested.push(ModMetadata {
+            mod_id: Some("fabric-networking-api-v1".to_string()),
+            version: Some("1.3.11".to_string()),
+            ..ModMetadata::default()
+        });
+        let installed = vec![
+            jar(
+                "lithium.jar",
+                "lithium",
+                "0.11.2",
+                &[
+                    ("fabric", VersionReq::Any),
+                    (
+                        "fabric-networking-api-v1",
+                        VersionReq::Predicates(vec![">=1.0".to_string()]),
+                    ),
+                    (
+                        "fabricloader",
+                        VersionReq::Predicates(vec![">=0.15".to_string()]),
+                    ),
+                ],
+            ),
+            fabric_api,
+        ];
+
+        assert!(find_problems(&installed, &[]).is_empty());
+    }
+
+    #[test]
+    fn ignores_client_side_dependencies_on_the_server() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file(
+                "META-INF/mods.toml",
+                zip::write::SimpleFileOptions::default(),
+            )
+            .unwrap();
+        writer
+            .write_all(
+                br#"
+[[mods]]
+modId = "create"
+
+[[dependencies.create]]
+modId = "ponder"
+mandatory = true
+side = "CLIENT"
+
+[[dependencies.create]]
+modId = "flywheel"
+type = "required"
+side = "BOTH"
+"#,
+            )
+            .unwrap();
+        let cursor = writer.finish().unwrap();
+        let metadata =
+            read_with_nested(Cursor::new(cursor.into_inner()), 0, &mut Vec::new()).unwrap();
+        let installed = vec![
+            InstalledMod {
+                file_name: "create.jar".to_string(),
+                metadata,
+                nested: Vec::new(),
+            },
+            jar("flywheel.jar", "flywheel", "0.6.10", &[]),
+        ];
+        let client_only = vec![jar("ponder.jar", "ponder", "1.0", &[])];
+
+        assert!(find_problems(&installed, &client_only).is_empty());
+    }
+}
@@ -1,23 +1,29 @@
+use crate::http::with_proxy;
 use crate::models::{ModInfo, ModMetadata};
-use crate::utils::sanitize_filename;
+use crate::ops::mod_cache::{self, SourceEntry};
+use crate::retry::{RetryPolicy, StatusError};
+use crate::utils::{BROWSER_USER_AGENT, sanitize_filename};
 use anyhow::{Context, Result};
 use futures::StreamExt;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use indicatif::{HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
 use reqwest::{
-    Client, Url,
+    Client, StatusCode, Url,
     cookie::Jar,
-    header::{HeaderMap, HeaderValue},
+    header::{
+        ACCEPT_RANGES, CONTENT_RANGE, ETAG, HeaderMap, HeaderValue, IF_RANGE, LAST_MODIFIED, RANGE,
+    },
 };
 use serde::Deserialize;
 use sha1::Sha1;
-use sha2::{Digest, Sha512};
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::{
-    Arc,
+    Arc, Mutex,
     atomic::{AtomicUsize, Ordering},
 };
 use std::time::Duration;
-use tokio::fs::{self, File};
+use tokio::fs::{self, File, OpenOptions};
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
 use tokio::time::sleep;
 
@@ -32,21 +38,89 @@ struct CfFileData {
     file_name: String,
 }
 
-const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
-
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum HashAlgorithm {
     Sha1,
+    Sha256,
     Sha512,
 }
 
+#[derive(Clone, Copy)]
+struct DownloadSettings {
+    skip_hash: bool,
+    retry: RetryPolicy,
+    /// Only restore from the mod cache; nothing is fetched over the network.
+    offline: bool,
+}
+
+enum ContentHasher {
+    None,
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl ContentHasher {
+    fn new(algorithm: Option<HashAlgorithm>) -> Self {
+        match algorithm {
+            None => ContentHasher::None,
+            Some(HashAlgorithm::Sha1) => ContentHasher::Sha1(Sha1::new()),
+            Some(HashAlgorithm::Sha256) => ContentHasher::Sha256(Sha256::new()),
+            Some(HashAlgorithm::Sha512) => ContentHasher::Sha512(Sha512::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            ContentHasher::None => {}
+            ContentHasher::Sha1(hasher) => hasher.update(data),
+            ContentHasher::Sha256(hasher) => hasher.update(data),
+            ContentHasher::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finalize(self) -> Option<String> {
+        match self {
+            ContentHasher::None => None,
+            ContentHasher::Sha1(hasher) => Some(hex::encode(hasher.finalize())),
+            ContentHasher::Sha256(hasher) => Some(hex::encode(hasher.finalize())),
+            ContentHasher::Sha512(hasher) => Some(hex::encode(hasher.finalize())),
+        }
+    }
+
+    /// Feeds the bytes already on disk, so a resumed download hashes the whole file.
+    async fn update_from_file(&mut self, path: &Path) -> Result<()> {
+        if matches!(self, ContentHasher::None) {
+            return Ok(());
+        }
+
+        let mut file = File::open(path).await?;
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = file.read(&mut buf).await?;
+            if n == 0 {
+                return Ok(());
+            }
+            self.update(&buf[..n]);
+        }
+    }
+}
+
+/// Returns the name each downloaded file ended up with, keyed by the file name the pack gave.
 pub async fn download_all(
     mods: Vec<ModInfo>,
     output_dir: PathBuf,
     parallel: usize,
     skip_hash: bool,
     proxy: Option<&str>,
-) -> Result<()> {
+    retry: RetryPolicy,
+    offline: bool,
+) -> Result<HashMap<String, String>> {
+    let settings = DownloadSettings {
+        skip_hash,
+        retry,
+        offline,
+    };
     let mods_dir = output_dir.join("mods");
     fs::create_dir_all(&mods_dir)
         .await
@@ -64,25 +138,21 @@ pub async fn download_all(
         HeaderValue::from_static("https://www.curseforge.com"),
     );
 
-    let mut client_builder = Client::builder()
+    let client_builder = Client::builder()
         .redirect(reqwest::redirect::Policy::limited(10))
         .cookie_provider(jar.clone())
-        .user_agent(USER_AGENT)
+        .user_agent(BROWSER_USER_AGENT)
         .tcp_nodelay(true)
         .pool_max_idle_per_host(50)
         .pool_idle_timeout(Duration::from_secs(15))
         .connect_timeout(Duration::from_secs(10))
         .default_headers(headers);
 
-    if let Some(proxy_url) = proxy {
-        let proxy =
-            reqwest::Proxy::all(proxy_url).context(format!("Invalid proxy URL: {}", proxy_url))?;
-        client_builder = client_builder.proxy(proxy);
+    let client = with_proxy(client_builder, proxy)?.build()?;
+    if !offline {
+        let _ = client.get("https://www.curseforge.com").send().await;
     }
 
-    let client = client_builder.build()?;
-    let _ = client.get("https://www.curseforge.com").send().await;
-
     let multi_pb = MultiProgress::new();
     let total_pb = multi_pb.add(ProgressBar::new(mods.len() as u64));
     total_pb.set_style(
@@ -101,27 +171,45 @@ pub async fn download_all(
     let bodies = futures::stream::iter(mods)
         .map(|mod_info| {
             let client = client.clone();
-            let mods_dir = mods_dir.clone();
+            let output_dir = output_dir.clone();
             let total_pb = total_pb.clone();
             let byte_pb = byte_pb.clone();
             async move {
-                download_single_mod(
-                    &client, &mod_info, &mods_dir, &total_pb, &byte_pb, skip_hash,
+                let result = download_single_mod(
+                    &client,
+                    &mod_info,
+                    &output_dir,
+                    &total_pb,
+                    &byte_pb,
+                    settings,
                 )
-                .await
+                .await;
+                (mod_info.file_name, result)
             }
         })
         .buffer_unordered(parallel);
 
     let error_count = AtomicUsize::new(0);
+    let missing = Mutex::new(Vec::new());
+    let final_names = Mutex::new(HashMap::new());
     bodies
-        .for_each(|res| async {
+        .for_each(|(file_name, res)| async {
             total_pb.inc(1);
-            if let Err(e) = res {
-                error_count.fetch_add(1, Ordering::SeqCst);
-                total_pb.suspend(|| {
-                    eprintln!("Download error: {:#}", e);
-                });
+            match res {
+                Ok(Some(final_name)) => {
+                    final_names.lock().unwrap().insert(file_name, final_name);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    error_count.fetch_add(1, Ordering::SeqCst);
+                    if offline {
+                        missing.lock().unwrap().push(format!("{:#}", e));
+                    } else {
+                        total_pb.suspend(|| {
+                            eprintln!("Download error: {:#}", e);
+                        });
+                    }
+                }
             }
         })
         .await;
@@ -130,40 +218,64 @@ pub async fn download_all(
     if failures > 0 {
         total_pb.finish_with_message("Some mods failed to download");
         byte_pb.finish_and_clear();
+        if offline {
+            let mut missing = missing.into_inner().unwrap();
+            missing.sort();
+            let list = missing
+                .iter()
+                .map(|item| format!("  - {}", item))
+                .collect::<Vec<_>>()
+                .join("\n");
+            anyhow::bail!(
+                "{} files are not available offline:\n{}\nRun `mcpacker prefetch <pack>` on a connected machine to cache them.",
+                failures,
+                list
+            );
+        }
         anyhow::bail!("{} mods failed to download", failures);
     }
 
     total_pb.finish_with_message("All mods downloaded!");
     byte_pb.finish_and_clear();
-    Ok(())
+    Ok(final_names.into_inner().unwrap())
 }
 
 async fn download_single_mod(
     client: &Client,
     mod_info: &ModInfo,
-    mods_dir: &Path,
+    output_dir: &Path,
     main_pb: &ProgressBar,
     byte_pb: &ProgressBar,
-    skip_hash: bool,
-) -> Result<()> {
+    settings: DownloadSettings,
+) -> Result<Option<String>> {
+    let DownloadSettings {
+        skip_hash, retry, ..
+    } = settings;
     main_pb.set_message(format!("Downloading: {}", mod_info.name));
 
+    let target_dir = install_dir(output_dir, mod_info);
+    fs::create_dir_all(&target_dir)
+        .await
+        .with_context(|| format!("Failed to create directory: {:?}", target_dir))?;
+    let skip_archives = mod_info.install_path.is_none();
+
     let mut target_filename = if mod_info.file_name.is_empty() {
         format!("{}.jar", mod_info.name)
     } else {
         sanitize_filename(&mod_info.file_name)
     };
-    let mut resolved_real_name = false;
+    let mut resolved_real_name = mod_info.install_path.is_some();
     let mut download_urls = mod_info.download_urls.clone();
+    let local_only =
+        !download_urls.is_empty() && download_urls.iter().all(|url| url.starts_with("file://"));
 
-    if let Some(first_url) = mod_info.download_urls.first()
+    if !settings.offline
+        && let Some(first_url) = mod_info.download_urls.first()
         && first_url.contains("curseforge.com/api")
     {
         let meta_url = first_url.trim_end_matches("/download");
-        if let Ok(resp) = client
-            .get(meta_url)
-            .header("Accept", "application/json")
-            .send()
+        if let Ok(resp) = retry
+            .send(client.get(meta_url).header("Accept", "application/json"))
             .await
         {
             if resp.status().is_success() {
@@ -185,12 +297,13 @@ async fn download_single_mod(
         }
     }
 
-    if resolved_real_name && target_filename.ends_with(".zip") {
+    if skip_archives && resolved_real_name && target_filename.ends_with(".zip") {
         main_pb.set_message(format!("Skipping resource pack: {}", target_filename));
-        return Ok(());
+        record_skipped(mod_info, &target_filename);
+        return Ok(None);
     }
 
-    let temp_file_path = mods_dir.join(format!("{}.part", target_filename));
+    let temp_file_path = target_dir.join(format!("{}.part", target_filename));
     let expected_hash = if skip_hash || mod_info.hash.is_empty() {
         None
     } else {
@@ -198,15 +311,49 @@ async fn download_single_mod(
             .map(|algorithm| (algorithm, mod_info.hash.as_str()))
     };
 
-    let current_file_path = mods_dir.join(&target_filename);
+    let current_file_path = target_dir.join(&target_filename);
     if current_file_path.exists()
         && (skip_hash
             || verify_hash(&current_file_path, expected_hash)
                 .await
                 .unwrap_or(false))
     {
+        discard_partial(&temp_file_path).await;
         main_pb.set_message(format!("Already exists: {}", target_filename));
-        return Ok(());
+        return Ok(Some(target_filename));
+    }
+
+    // The cache is keyed by content, so the pack's hash finds the file even under --skip-hash.
+    if !mod_info.hash.is_empty()
+        && mod_cache::restore(&mod_info.hash_algo, &mod_info.hash, &current_file_path)
+            .unwrap_or(false)
+    {
+        if verify_hash(&current_file_path, expected_hash)
+            .await
+            .unwrap_or(false)
+        {
+            discard_partial(&temp_file_path).await;
+            main_pb.set_message(format!("From cache: {}", target_filename));
+            return Ok(Some(target_filename));
+        }
+        let _ = fs::remove_file(&current_file_path).await;
+        mod_cache::evict(&mod_info.hash_algo, &mod_info.hash);
+    }
+
+    if !local_only && let Some(restored) = restore_from_source(mod_info, &target_dir, main_pb) {
+        discard_partial(&temp_file_path).await;
+        return Ok(restored);
+    }
+    if settings.offline && !local_only {
+        main_pb.set_message(format!("Not cached: {}", mod_info.name));
+        anyhow::bail!(
+            "{} ({})",
+            mod_info.name,
+            mod_info
+                .download_urls
+                .first()
+                .map_or("no download URL", String::as_str)
+        );
     }
 
     main_pb.set_message(format!("Downloading: {}", target_filename));
@@ -214,8 +361,6 @@ async fn download_single_mod(
     let mut last_error = None;
     for (url_index, url) in download_urls.iter().enumerate() {
         if url_index > 0 {
-            let wait = Duration::from_millis(500 * url_index as u64);
-            sleep(wait).await;
             main_pb.set_message(format!(
                 "Retrying: {} (node {})",
                 target_filename,
@@ -223,17 +368,37 @@ async fn download_single_mod(
             ));
         }
 
-        match try_download_from_url(
-            client,
-            url,
-            &mod_info.name,
-            &temp_file_path,
-            main_pb,
-            byte_pb,
-            expected_hash,
-        )
-        .await
-        {
+        let mut attempt = 1;
+        let result = loop {
+            let result = try_download_from_url(
+                client,
+                url,
+                &mod_info.name,
+                &temp_file_path,
+                main_pb,
+                byte_pb,
+                expected_hash,
+            )
+            .await;
+            let Err(e) = &result else {
+                break result;
+            };
+            let Some(delay) = retry.delay_after(attempt, e) else {
+                break result;
+            };
+
+            attempt += 1;
+            main_pb.set_message(format!(
+                "Retrying: {} in {} (attempt {}/{})",
+                target_filename,
+                HumanDuration(delay),
+                attempt,
+                retry.max_attempts
+            ));
+            sleep(delay).await;
+        };
+
+        match result {
             Ok((final_url, hash_ok)) => {
                 if !resolved_real_name
                     && let Some(real_name) = extract_filename_from_url(&final_url)
@@ -242,30 +407,39 @@ async fn download_single_mod(
                     resolved_real_name = true;
                 }
 
-                if target_filename.ends_with(".zip") {
+                if skip_archives && target_filename.ends_with(".zip") {
                     main_pb.set_message(format!("Skipping resource pack: {}", target_filename));
-                    if temp_file_path.exists() {
-                        let _ = fs::remove_file(&temp_file_path).await;
-                    }
-                    return Ok(());
+                    discard_partial(&temp_file_path).await;
+                    record_skipped(mod_info, &target_filename);
+                    return Ok(None);
                 }
 
                 main_pb.set_message(format!("Verifying: {}", target_filename));
 
                 if skip_hash || hash_ok {
-                    let final_path = mods_dir.join(&target_filename);
+                    let mut final_path = target_dir.join(&target_filename);
                     if final_path.exists() {
                         let _ = fs::remove_file(&final_path).await;
                     }
                     fs::rename(&temp_file_path, &final_path).await?;
-
-                    if !resolved_real_name && target_filename.starts_with("CF-") {
-                        let _ =
-                            rename_with_metadata(&mod_info.name, &final_path, &final_path).await;
+                    let _ = fs::remove_file(resume_validator_path(&temp_file_path)).await;
+
+                    if !resolved_real_name
+                        && target_filename.starts_with("CF-")
+                        && let Ok(renamed) =
+                            rename_with_metadata(&mod_info.name, &final_path, &final_path).await
+                    {
+                        final_path = renamed;
                     }
+
+                    cache_download(mod_info, &final_path, expected_hash.is_some() && hash_ok).await;
                     main_pb.set_message(format!("Completed: {}", target_filename));
-                    return Ok(());
+                    return Ok(final_path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned()));
                 } else {
+                    // A corrupt prefix must not be resumed from the next URL.
+                    discard_partial(&temp_file_path).await;
                     last_error = Some(anyhow::anyhow!("Hash mismatch"));
                 }
             }
@@ -273,14 +447,83 @@ async fn download_single_mod(
         }
     }
 
-    if temp_file_path.exists() {
-        let _ = fs::remove_file(&temp_file_path).await;
+    // Keep what was downloaded for the next run when the server allows resuming it.
+    if !resume_validator_path(&temp_file_path).exists() {
+        discard_partial(&temp_file_path).await;
     }
 
     main_pb.set_message(format!("Failed: {}", mod_info.name));
     Err(last_error.unwrap_or_else(|| anyhow::anyhow!("All nodes failed to download")))
 }
 
+/// Restores a file the mod cache recorded under its download URL, for packs without file hashes.
+/// `Some(None)` means the URL resolved to a resource pack that is skipped.
+fn restore_from_source(
+    mod_info: &ModInfo,
+    target_dir: &Path,
+    main_pb: &ProgressBar,
+) -> Option<Option<String>> {
+    let entry = mod_cache::lookup_source(mod_info.download_urls.first()?)?;
+    let file_name = sanitize_filename(&entry.file_name);
+    let Some(sha512) = entry.sha512 else {
+        main_pb.set_message(format!("Skipping resource pack: {}", file_name));
+        return Some(None);
+    };
+    if !mod_cache::restore("sha512", &sha512, &target_dir.join(&file_name)).unwrap_or(false) {
+        return None;
+    }
+    main_pb.set_message(format!("From cache: {}", file_name));
+    Some(Some(file_name))
+}
+
+/// The URL that identifies a download without a pack hash in the mod cache.
+fn cache_source(mod_info: &ModInfo) -> Option<&str> {
+    if !mod_info.hash.is_empty() {
+        return None;
+    }
+    mod_info
+        .download_urls
+        .first()
+        .map(String::as_str)
+        .filter(|url| !url.starts_with("file://"))
+}
+
+/// Adds a finished download to the mod cache; files without a pack hash are found again by URL.
+async fn cache_download(mod_info: &ModInfo, path: &Path, verified: bool) {
+    let source_url = cache_source(mod_info).map(str::to_string);
+    if !verified && source_url.is_none() {
+        return;
+    }
+
+    let path = path.to_path_buf();
+    let _ = tokio::task::spawn_blocking(move || {
+        let sha512 = mod_cache::store(&path)?;
+        if let Some(source_url) = source_url {
+            let entry = SourceEntry {
+                file_name: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                sha512: Some(sha512),
+            };
+            mod_cache::record_source(&source_url, &entry)?;
+        }
+        Ok::<_, anyhow::Error>(())
+    })
+    .await;
+}
+
+/// Remembers that a URL resolved to a resource pack, so offline runs skip it too.
+fn record_skipped(mod_info: &ModInfo, file_name: &str) {
+    if let Some(source_url) = cache_source(mod_info) {
+        let entry = SourceEntry {
+            file_name: file_name.to_string(),
+            sha512: None,
+        };
+        let _ = mod_cache::record_source(source_url, &entry);
+    }
+}
+
 async fn try_download_from_url(
     client: &Client,
     url: &str,
@@ -290,17 +533,45 @@ async fn try_download_from_url(
     byte_pb: &ProgressBar,
     expected_hash: Option<(HashAlgorithm, &str)>,
 ) -> Result<(String, bool)> {
-    let response = client.get(url).header("Accept", "*/*").send().await?;
+    if url.starts_with("file://") {
+        return copy_from_local_url(url, file_path, byte_pb, expected_hash).await;
+    }
+
+    let resume = resume_point(file_path).await;
+    let mut request = client.get(url).header("Accept", "*/*");
+    if let Some((offset, validator)) = &resume {
+        request = request
+            .header(RANGE, format!("bytes={}-", offset))
+            .header(IF_RANGE, validator);
+    }
+    let mut response = request.send().await?;
+
+    if resume.is_some() && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        discard_partial(file_path).await;
+        response = client.get(url).header("Accept", "*/*").send().await?;
+    }
 
     if !response.status().is_success() {
-        anyhow::bail!(
-            "Download failed [{}]: HTTP {} ({})",
-            mod_name,
-            response.status(),
-            url
-        );
+        return Err(anyhow::Error::new(StatusError::from_response(&response))
+            .context(format!("Download failed [{}] ({})", mod_name, url)));
     }
 
+    let offset = match resume {
+        Some((offset, _)) if response.status() == StatusCode::PARTIAL_CONTENT => {
+            if content_range_start(response.headers()) != Some(offset) {
+                discard_partial(file_path).await;
+                anyhow::bail!(
+                    "Download failed [{}]: server resumed at the wrong offset ({})",
+                    mod_name,
+                    url
+                );
+            }
+            offset
+        }
+        // A 200 means the file changed or the server ignored the range; start over.
+        _ => 0,
+    };
+
     if let Some(disposition) = response.headers().get(reqwest::header::CONTENT_DISPOSITION)
         && let Ok(disp_str) = disposition.to_str()
         && let Some(real_name) = parse_content_disposition(disp_str)
@@ -308,46 +579,71 @@ async fn try_download_from_url(
         main_pb.set_message(format!("Downloading: {}", real_name));
     }
 
-    let content_length = response.content_length();
     let final_url = response.url().to_string();
-    let file = File::create(file_path).await?;
-    if let Some(len) = content_length {
-        let _ = file.set_len(len).await;
-    }
+    let mut hasher = ContentHasher::new(expected_hash.map(|(algorithm, _)| algorithm));
+    let file = if offset > 0 {
+        main_pb.set_message(format!("Resuming: {} at {}", mod_name, HumanBytes(offset)));
+        hasher.update_from_file(file_path).await?;
+        OpenOptions::new().append(true).open(file_path).await?
+    } else {
+        let file = File::create(file_path).await?;
+        match if_range_validator(response.headers()) {
+            Some(validator) => fs::write(resume_validator_path(file_path), validator).await?,
+            None => {
+                let _ = fs::remove_file(resume_validator_path(file_path)).await;
+            }
+        }
+        file
+    };
     let mut writer = BufWriter::new(file);
     let mut stream = response.bytes_stream();
 
-    let mut sha1_hasher = Sha1::new();
-    let mut sha512_hasher = Sha512::new();
-    let do_hash = expected_hash.is_some();
-
     while let Some(item) = stream.next().await {
         let chunk = item?;
         writer.write_all(&chunk).await?;
-        if let Some((algorithm, _)) = expected_hash {
-            match algorithm {
-                HashAlgorithm::Sha1 => sha1_hasher.update(&chunk),
-                HashAlgorithm::Sha512 => sha512_hasher.update(&chunk),
-            }
-        }
+        hasher.update(&chunk);
         byte_pb.inc(chunk.len() as u64);
     }
     writer.flush().await?;
 
-    let hash_ok = if do_hash {
-        let (algorithm, expected) = expected_hash.expect("hash is present when hashing is enabled");
-        let computed = match algorithm {
-            HashAlgorithm::Sha1 => hex::encode(sha1_hasher.finalize()),
-            HashAlgorithm::Sha512 => hex::encode(sha512_hasher.finalize()),
-        };
-        hashes_match(&computed, expected)
-    } else {
-        true
+    let hash_ok = match (hasher.finalize(), expected_hash) {
+        (Some(computed), Some((_, expected))) => hashes_match(&computed, expected),
+        _ => true,
     };
 
     Ok((final_url, hash_ok))
 }
 
+async fn copy_from_local_url(
+    url: &str,
+    file_path: &Path,
+    byte_pb: &ProgressBar,
+    expected_hash: Option<(HashAlgorithm, &str)>,
+) -> Result<(String, bool)> {
+    let source = Url::parse(url)
+        .ok()
+        .and_then(|url| url.to_file_path().ok())
+        .with_context(|| format!("Invalid local file URL: {}", url))?;
+    let copied = fs::copy(&source, file_path)
+        .await
+        .with_context(|| format!("Failed to copy local file: {:?}", source))?;
+    byte_pb.inc(copied);
+
+    let hash_ok = verify_hash(file_path, expected_hash).await?;
+    Ok((url.to_string(), hash_ok))
+}
+
+fn install_dir(output_dir: &Path, mod_info: &ModInfo) -> PathBuf {
+    match mod_info
+        .install_path
+        .as_ref()
+        .and_then(|path| path.parent())
+    {
+        Some(parent) => output_dir.join(parent),
+        None => output_dir.join("mods"),
+    }
+}
+
 fn parse_content_disposition(header: &str) -> Option<String> {
     header
         .split(';')
@@ -396,30 +692,70 @@ async fn verify_hash(path: &Path, expected_hash: Option<(HashAlgorithm, &str)>)
         return Ok(true);
     };
 
-    let mut file = File::open(path).await?;
-    let mut hasher_sha512 = Sha512::new();
-    let mut hasher_sha1 = sha1::Sha1::new();
-    let mut buf = [0u8; 8192];
+    let mut hasher = ContentHasher::new(Some(algorithm));
+    hasher.update_from_file(path).await?;
+    let computed = hasher
+        .finalize()
+        .expect("a hasher with an algorithm produces a digest");
+    Ok(hashes_match(&computed, expected_hash))
+}
 
-    loop {
-        let n = file.read(&mut buf).await?;
-        if n == 0 {
-            break;
-        }
-        match algorithm {
-            HashAlgorithm::Sha512 => hasher_sha512.update(&buf[..n]),
-            HashAlgorithm::Sha1 => hasher_sha1.update(&buf[..n]),
-        }
+/// Holds the `If-Range` validator for a `.part` file that can be resumed.
+fn resume_validator_path(part_path: &Path) -> PathBuf {
+    part_path.with_extension("part.range")
+}
+
+async fn discard_partial(part_path: &Path) {
+    let _ = fs::remove_file(part_path).await;
+    let _ = fs::remove_file(resume_validator_path(part_path)).await;
+}
+
+/// Returns the length of a resumable `.part` file and the validator it was started with.
+async fn resume_point(part_path: &Path) -> Option<(u64, String)> {
+    let validator = fs::read_to_string(resume_validator_path(part_path))
+        .await
+        .ok()?;
+    let len = fs::metadata(part_path).await.ok()?.len();
+    (len > 0 && !validator.trim().is_empty()).then(|| (len, validator.trim().to_string()))
+}
+
+/// Picks a validator for `If-Range` when the server accepts byte ranges.
+/// Weak ETags are not allowed in `If-Range`, so those fall back to `Last-Modified`.
+fn if_range_validator(headers: &HeaderMap) -> Option<String> {
+    let accepts_bytes = headers
+        .get(ACCEPT_RANGES)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|unit| unit.trim() == "bytes"));
+    if !accepts_bytes {
+        return None;
     }
 
-    let computed = match algorithm {
-        HashAlgorithm::Sha512 => hex::encode(hasher_sha512.finalize()),
-        HashAlgorithm::Sha1 => hex::encode(hasher_sha1.finalize()),
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|value: &HeaderValue| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
     };
-    Ok(hashes_match(&computed, expected_hash))
+    header(ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(LAST_MODIFIED))
+        .map(str::to_string)
 }
 
-async fn rename_with_metadata(mod_name: &str, temp_path: &Path, final_path: &Path) -> Result<()> {
+/// Parses the first byte position of a `Content-Range: bytes <start>-<end>/<len>` header.
+fn content_range_start(headers: &HeaderMap) -> Option<u64> {
+    let value = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    let (start, _) = value.trim().strip_prefix("bytes ")?.split_once('-')?;
+    start.trim().parse().ok()
+}
+
+/// Renames a jar after its metadata and returns where the file ended up.
+async fn rename_with_metadata(
+    mod_name: &str,
+    temp_path: &Path,
+    final_path: &Path,
+) -> Result<PathBuf> {
     if let Ok(meta) = ModMetadata::extract_from_jar(temp_path) {
         let final_name = format!(
             "{}-{}.jar",
@@ -431,8 +767,9 @@ async fn rename_with_metadata(mod_name: &str, temp_path: &Path, final_path: &Pat
             let _ = fs::remove_file(&target).await;
         }
         fs::rename(temp_path, &target).await?;
+        return Ok(target);
     }
-    Ok(())
+    Ok(final_path.to_path_buf())
 }
 
 fn hashes_match(computed: &str, expected: &str) -> bool {
@@ -443,6 +780,7 @@ fn parse_hash_algorithm(algo: &str) -> Result<Option<HashAlgorithm>> {
     match algo.to_ascii_lowercase().as_str() {
         "" | "none" => Ok(None),
         "sha1" => Ok(Some(HashAlgorithm::Sha1)),
+        "sha256" => Ok(Some(HashAlgorithm::Sha256)),
         "sha512" => Ok(Some(HashAlgorithm::Sha512)),
         _ => anyhow::bail!("Unsupported hash algorithm: {}", algo),
     }
@@ -450,7 +788,48 @@ fn parse_hash_algorithm(algo: &str) -> Result<Option<HashAlgorithm>> {
 
 #[cfg(test)]
 mod tests {
-    use super::{HashAlgorithm, hashes_match, parse_hash_algorithm};
+    use super::{
+        HashAlgorithm, content_range_start, hashes_match, if_range_validator, install_dir,
+        parse_hash_algorithm,
+    };
+    use crate::models::{ModInfo, SideType};
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use std::path::{Path, PathBuf};
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    fn mod_info(install_path: Option<&str>) -> ModInfo {
+        ModInfo {
+            name: "example".to_string(),
+            file_name: "example.jar".to_string(),
+            download_urls: Vec::new(),
+            hash: String::new(),
+            hash_algo: "none".to_string(),
+            side: SideType::Both,
+            is_required: true,
+            install_path: install_path.map(PathBuf::from),
+        }
+    }
+
+    #[test]
+    fn routes_files_to_their_declared_directory() {
+        let output = Path::new("server");
+
+        assert_eq!(
+            install_dir(output, &mod_info(Some("config/example.toml"))),
+            output.join("config")
+        );
+        assert_eq!(
+            install_dir(output, &mod_info(Some("shaderpacks/example.zip"))),
+            output.join("shaderpacks")
+        );
+        assert_eq!(install_dir(output, &mod_info(None)), output.join("mods"));
+    }
 
     #[test]
     fn compares_hashes_case_insensitively() {
@@ -464,6 +843,10 @@ mod tests {
             parse_hash_algorithm("SHA1").unwrap(),
             Some(HashAlgorithm::Sha1)
         );
+        assert_eq!(
+            parse_hash_algorithm("sha256").unwrap(),
+            Some(HashAlgorithm::Sha256)
+        );
         assert_eq!(
             parse_hash_algorithm("sha512").unwrap(),
             Some(HashAlgorithm::Sha512)
@@ -471,4 +854,41 @@ mod tests {
         assert_eq!(parse_hash_algorithm("none").unwrap(), None);
         assert!(parse_hash_algorithm("md5").is_err());
     }
+
+    #[test]
+    fn only_resumes_when_the_server_accepts_ranges() {
+        assert_eq!(
+            if_range_validator(&headers(&[("accept-ranges", "bytes"), ("etag", "\"abc\"")])),
+            Some("\"abc\"".to_string())
+        );
+        assert_eq!(
+            if_range_validator(&headers(&[
+                ("accept-ranges", "bytes"),
+                ("etag", "W/\"abc\""),
+                ("last-modified", "Wed, 21 Oct 2015 07:28:00 GMT"),
+            ])),
+            Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string())
+        );
+        assert_eq!(
+            if_range_validator(&headers(&[("accept-ranges", "none"), ("etag", "\"abc\"")])),
+            None
+        );
+        assert_eq!(
+            if_range_validator(&headers(&[("accept-ranges", "bytes")])),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_content_range_start() {
+        assert_eq!(
+            content_range_start(&headers(&[("content-range", "bytes 1024-2047/2048")])),
+            Some(1024)
+        );
+        assert_eq!(
+            content_range_start(&headers(&[("content-range", "bytes */2048")])),
+            None
+        );
+        assert_eq!(content_range_start(&HeaderMap::new()), None);
+    }
 }
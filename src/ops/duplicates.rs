@@ -0,0 +1,248 @@
+use crate::models::version::compare_versions;
+use crate::ops::dependencies::scan_jars;
+use anyhow::{Context, Result};
+use sha1::{Digest, Sha1};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::Path;
+
+pub const DUPLICATE_MODS_DIR: &str = "duplicate-mods";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DuplicatePolicy {
+    /// List the duplicates and leave every jar in place
+    Warn,
+    /// Keep the jar with the highest version
+    KeepNewest,
+    /// Keep the jar downloaded from the pack index over override copies
+    KeepIndex,
+    /// Stop and list the duplicates
+    Fail,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateJar {
+    pub file_name: String,
+    pub version: String,
+    pub from_index: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    pub mod_id: String,
+    pub jars: Vec<DuplicateJar>,
+}
+
+impl DuplicateGroup {
+    pub fn keeper(&self, policy: DuplicatePolicy) -> &DuplicateJar {
+        let prefer_index =
+            policy == DuplicatePolicy::KeepIndex && self.jars.iter().any(|jar| jar.from_index);
+
+        self.jars
+            .iter()
+            .filter(|jar| !prefer_index || jar.from_index)
+            .reduce(|best, jar| {
+                if compare_versions(&jar.version, &best.version).is_gt() {
+                    jar
+                } else {
+                    best
+                }
+            })
+            .expect("duplicate groups hold at least two jars")
+    }
+}
+
+impl fmt::Display for DuplicateGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let jars = self
+            .jars
+            .iter()
+            .map(|jar| {
+                let origin = if jar.from_index { "index" } else { "overrides" };
+                format!("{} ({}, {})", jar.file_name, jar.version, origin)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "{}: {}", self.mod_id, jars)
+    }
+}
+
+/// SHA-1 of every jar in `mods/`, so index copies are recognized by content rather than by name.
+pub fn snapshot_jars(output_dir: &Path) -> HashSet<String> {
+    std::fs::read_dir(output_dir.join("mods"))
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "jar"))
+                .filter_map(|path| jar_sha1(&path))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn jar_sha1(path: &Path) -> Option<String> {
+    let mut file = std::fs::File::open(path).ok()?;
+    copy_with_sha1(&mut file, &mut std::io::sink()).ok()
+}
+
+/// Copies `reader` into `writer` and returns the SHA-1 of what went through, chunk by chunk.
+pub fn copy_with_sha1(reader: &mut dyn Read, writer: &mut dyn Write) -> std::io::Result<String> {
+    let mut hasher = Sha1::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        writer.write_all(&buf[..n])?;
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+pub async fn find_duplicates(
+    output_dir: &Path,
+    index_jars: HashSet<String>,
+) -> Result<Vec<DuplicateGroup>> {
+    let mods_dir = output_dir.join("mods");
+
+    tokio::task::spawn_blocking(move || {
+        let mut by_id: BTreeMap<String, Vec<DuplicateJar>> = BTreeMap::new();
+        for installed in scan_jars(&mods_dir) {
+            let Some(mod_id) = installed.metadata.mod_id else {
+                continue;
+            };
+            let from_index = jar_sha1(&mods_dir.join(&installed.file_name))
+                .is_some_and(|sha1| index_jars.contains(&sha1));
+            by_id.entry(mod_id).or_default().push(DuplicateJar {
+                from_index,
+                file_name: installed.file_name,
+                version: installed.metadata.version.unwrap_or_default(),
+            });
+        }
+
+        by_id
+            .into_iter()
+            .filter(|(_, jars)| jars.len() > 1)
+            .map(|(mod_id, jars)| DuplicateGroup { mod_id, jars })
+            .collect()
+    })
+    .await
+    .map_err(Into::into)
+}
+
+pub fn move_duplicates(
+    output_dir: &Path,
+    groups: &[DuplicateGroup],
+    policy: DuplicatePolicy,
+) -> Result<()> {
+    let duplicate_dir = output_dir.join(DUPLICATE_MODS_DIR);
+
+    for group in groups {
+        let keeper = group.keeper(policy);
+        for jar in group.jars.iter().filter(|jar| *jar != keeper) {
+            std::fs::create_dir_all(&duplicate_dir)?;
+            let path = output_dir.join("mods").join(&jar.file_name);
+            std::fs::rename(&path, duplicate_dir.join(&jar.file_name))
+                .with_context(|| format!("Failed to move duplicate mod: {:?}", path))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DuplicateGroup, DuplicateJar, DuplicatePolicy, find_duplicates, snapshot_jars};
+    use crate::test_support::{temp_dir, write_zip};
+    use std::path::Path;
+
+    fn write_jar(path: &Path, version: &str) {
+        let fabric_mod = format!(r#"{{"id": "sodium", "version": "{}"}}"#, version);
+        write_zip(path, &[("fabric.mod.json", &fabric_mod)]);
+    }
+
+    fn group() -> DuplicateGroup {
+        DuplicateGroup {
+            mod_id: "sodium".to_string(),
+            jars: vec![
+                DuplicateJar {
+                    file_name: "sodium-0.5.3.jar".to_string(),
+                    version: "0.5.3".to_string(),
+                    from_index: true,
+                },
+                DuplicateJar {
+                    file_name: "sodium-0.5.8.jar".to_string(),
+                    version: "0.5.8".to_string(),
+                    from_index: false,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn picks_keeper_by_policy() {
+        let group = group();
+
+        assert_eq!(
+            group.keeper(DuplicatePolicy::KeepNewest).file_name,
+            "sodium-0.5.8.jar"
+        );
+        assert_eq!(
+            group.keeper(DuplicatePolicy::KeepIndex).file_name,
+            "sodium-0.5.3.jar"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_newest_when_no_copy_came_from_the_index() {
+        let mut group = group();
+        group.jars[0].from_index = false;
+
+        assert_eq!(
+            group.keeper(DuplicatePolicy::KeepIndex).file_name,
+            "sodium-0.5.8.jar"
+        );
+    }
+
+    #[test]
+    fn lists_versions_and_origins() {
+        assert_eq!(
+            group().to_string(),
+            "sodium: sodium-0.5.3.jar (0.5.3, index), sodium-0.5.8.jar (0.5.8, overrides)"
+        );
+    }
+
+    #[tokio::test]
+    async fn recognizes_index_copies_by_content() {
+        let output_dir = temp_dir();
+        let mods_dir = output_dir.path().join("mods");
+        std::fs::create_dir_all(&mods_dir).unwrap();
+        write_jar(&mods_dir.join("sodium.jar"), "0.5.3");
+        let index_jars = snapshot_jars(output_dir.path());
+
+        // An override that reuses the index file name is still an override.
+        std::fs::rename(
+            mods_dir.join("sodium.jar"),
+            mods_dir.join("sodium-index.jar"),
+        )
+        .unwrap();
+        write_jar(&mods_dir.join("sodium.jar"), "0.5.8");
+
+        let groups = find_duplicates(output_dir.path(), index_jars)
+            .await
+            .unwrap();
+        let mut jars = groups[0].jars.clone();
+        jars.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(
+            jars.iter()
+                .map(|jar| (jar.file_name.as_str(), jar.from_index))
+                .collect::<Vec<_>>(),
+            vec![("sodium-index.jar", true), ("sodium.jar", false)]
+        );
+    }
+}
@@ -1,24 +1,24 @@
 use crate::models::{LoaderType, ServerContext};
+use crate::ops::duplicates;
+use crate::parsers::{PackFormat, detect_format, packwiz, prism, source};
 use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::io::Read;
 use std::path::Path;
 use tokio::fs::{File, OpenOptions};
 use tokio::io::AsyncWriteExt;
-use zip::ZipArchive;
 
 use crate::ui::print_step;
+use crate::utils::is_safe_relative_path;
 
 pub async fn generate_server_files(
     context: &ServerContext,
-    pack_path: &Path,
     output_dir: &Path,
     memory: &str,
     server_jar: &str,
     java_path: &str,
     accept_eula: bool,
 ) -> Result<String> {
-    print_step("Extracting overrides");
-    extract_overrides(pack_path, output_dir).await?;
-
     print_step("Generating eula.txt");
     let eula_path = output_dir.join("eula.txt");
     let mut eula_file = File::create(eula_path).await?;
@@ -35,38 +35,58 @@ pub async fn generate_server_files(
     Ok(script_name)
 }
 
-async fn extract_overrides(pack_path: &Path, output_dir: &Path) -> Result<()> {
+const OVERRIDE_LAYERS: [&str; 2] = ["overrides/", "server-overrides/"];
+
+/// `index_jars` holds the SHA-1 of every jar the pack index put in `mods/`, from
+/// `duplicates::snapshot_jars`.
+pub async fn extract_overrides(
+    pack_path: &Path,
+    output_dir: &Path,
+    index_jars: &HashSet<String>,
+) -> Result<()> {
     let pack_path = pack_path.to_path_buf();
     let output_dir = output_dir.to_path_buf();
+    let index_jars = index_jars.clone();
 
     tokio::task::spawn_blocking(move || {
-        let file = std::fs::File::open(&pack_path)
-            .with_context(|| format!("Failed to open pack file: {:?}", pack_path))?;
-        let mut archive = ZipArchive::new(file)?;
-
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
-            let name = file.name().to_string();
-
-            if name.starts_with("overrides/") && !name.ends_with('/') {
-                let relative_path = name.trim_start_matches("overrides/");
-
-                let path = std::path::Path::new(relative_path);
-                if path
-                    .components()
-                    .any(|c| !matches!(c, std::path::Component::Normal(_)))
-                {
-                    anyhow::bail!("Malicious path detected in modpack: {}", name);
-                }
+        let format = detect_format(&pack_path)?;
+        if format == PackFormat::Packwiz {
+            for (source, relative_path) in packwiz::override_files(&pack_path)? {
+                let mut file = std::fs::File::open(&source)
+                    .with_context(|| format!("Failed to copy override file: {:?}", source))?;
+                write_override(&output_dir, &relative_path, &index_jars, &mut file)?;
+            }
+            return Ok(());
+        }
 
-                let dest_path = output_dir.join(relative_path);
+        let prism_game_dir = if format == PackFormat::Prism {
+            Some(prism::game_dir_prefix(&pack_path)?)
+        } else {
+            None
+        };
+        let layers = match &prism_game_dir {
+            Some(game_dir) => vec![game_dir.as_str()],
+            None => OVERRIDE_LAYERS.to_vec(),
+        };
 
-                if let Some(parent) = dest_path.parent() {
-                    std::fs::create_dir_all(parent)?;
+        let mut source = source::open(&pack_path)?;
+        let names = source.file_names();
+
+        for prefix in layers {
+            for name in &names {
+                let Some(relative_path) = override_relative_path(name, prefix)? else {
+                    continue;
+                };
+                if prism_game_dir.is_some() && !prism::is_server_override(&name[prefix.len()..]) {
+                    continue;
                 }
 
-                let mut outfile = std::fs::File::create(&dest_path)?;
-                std::io::copy(&mut file, &mut outfile)?;
+                write_override(
+                    &output_dir,
+                    relative_path,
+                    &index_jars,
+                    &mut source.open_file(name)?,
+                )?;
             }
         }
         Ok::<(), anyhow::Error>(())
@@ -76,6 +96,98 @@ async fn extract_overrides(pack_path: &Path, output_dir: &Path) -> Result<()> {
     Ok(())
 }
 
+/// An override never replaces a different index jar of the same name; it is written next to it
+/// as `<name>-overrides.jar`, so the duplicate check reports both copies.
+fn write_override(
+    output_dir: &Path,
+    relative_path: &Path,
+    index_jars: &HashSet<String>,
+    reader: &mut dyn Read,
+) -> Result<()> {
+    let dest_path = output_dir.join(relative_path);
+    let parent = dest_path.parent().unwrap_or(output_dir);
+    std::fs::create_dir_all(parent)?;
+
+    if relative_path.starts_with("mods")
+        && let Some(index_sha1) =
+            duplicates::jar_sha1(&dest_path).filter(|sha1| index_jars.contains(sha1))
+    {
+        let mut staged = tempfile::NamedTempFile::new_in(parent)?;
+        let sha1 = duplicates::copy_with_sha1(reader, staged.as_file_mut())?;
+        if sha1 == index_sha1 {
+            return Ok(());
+        }
+        let stem = relative_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let override_path = dest_path.with_file_name(format!("{}-overrides.jar", stem));
+        staged
+            .persist(&override_path)
+            .with_context(|| format!("Failed to write override file: {:?}", override_path))?;
+        return Ok(());
+    }
+
+    let mut outfile = std::fs::File::create(&dest_path)
+        .with_context(|| format!("Failed to write override file: {:?}", dest_path))?;
+    std::io::copy(reader, &mut outfile)?;
+    Ok(())
+}
+
+pub async fn extract_server_pack(pack_path: &Path, output_dir: &Path) -> Result<()> {
+    let pack_path = pack_path.to_path_buf();
+    let output_dir = output_dir.to_path_buf();
+
+    tokio::task::spawn_blocking(move || {
+        let mut source = source::open(&pack_path)?;
+        let names = source.file_names();
+        let prefix = shared_root_folder(&names).unwrap_or_default();
+
+        for name in &names {
+            let Some(relative_path) = override_relative_path(name, &prefix)? else {
+                continue;
+            };
+
+            let dest_path = output_dir.join(relative_path);
+            if let Some(parent) = dest_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            let mut outfile = std::fs::File::create(&dest_path)?;
+            std::io::copy(&mut source.open_file(name)?, &mut outfile)?;
+        }
+        Ok::<(), anyhow::Error>(())
+    })
+    .await??;
+
+    Ok(())
+}
+
+fn shared_root_folder(names: &[String]) -> Option<String> {
+    let (first, _) = names.first()?.split_once('/')?;
+    let prefix = format!("{}/", first);
+    names
+        .iter()
+        .all(|name| name.starts_with(&prefix))
+        .then_some(prefix)
+}
+
+fn override_relative_path<'a>(name: &'a str, prefix: &str) -> Result<Option<&'a Path>> {
+    let Some(relative_path) = name.strip_prefix(prefix) else {
+        return Ok(None);
+    };
+    if relative_path.is_empty() || name.ends_with('/') {
+        return Ok(None);
+    }
+
+    let path = Path::new(relative_path);
+    if !is_safe_relative_path(path) {
+        anyhow::bail!("Malicious path detected in modpack: {}", name);
+    }
+
+    Ok(Some(path))
+}
+
 async fn generate_start_scripts(
     context: &ServerContext,
     output_dir: &Path,
@@ -193,3 +305,102 @@ async fn generate_start_scripts(
         Ok("start.sh".to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{OVERRIDE_LAYERS, extract_overrides, override_relative_path, shared_root_folder};
+    use crate::ops::duplicates::snapshot_jars;
+    use crate::test_support::{temp_dir, write_zip};
+
+    #[tokio::test]
+    async fn applies_server_overrides_after_common_overrides() {
+        let root = temp_dir();
+        let output_dir = root.path().join("server");
+        std::fs::create_dir_all(&output_dir).unwrap();
+
+        // server-overrides/ comes first in the archive, so only the layer order can put it on top.
+        let pack_path = root.path().join("pack.mrpack");
+        write_zip(
+            &pack_path,
+            &[
+                ("modrinth.index.json", "{}"),
+                ("server-overrides/server.properties", "motd=server"),
+                ("overrides/server.properties", "motd=common"),
+                ("overrides/config/a.toml", "a = 1"),
+            ],
+        );
+
+        extract_overrides(&pack_path, &output_dir, &snapshot_jars(&output_dir))
+            .await
+            .unwrap();
+        let properties = std::fs::read_to_string(output_dir.join("server.properties")).unwrap();
+        let config = std::fs::read_to_string(output_dir.join("config/a.toml")).unwrap();
+
+        assert_eq!(properties, "motd=server");
+        assert_eq!(config, "a = 1");
+    }
+
+    #[test]
+    fn never_extracts_client_overrides() {
+        for prefix in OVERRIDE_LAYERS {
+            assert_eq!(
+                override_relative_path("client-overrides/options.txt", prefix).unwrap(),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn skips_directories_and_rejects_traversal() {
+        assert_eq!(
+            override_relative_path("overrides/config/", "overrides/").unwrap(),
+            None
+        );
+        assert!(override_relative_path("overrides/../evil.sh", "overrides/").is_err());
+    }
+
+    #[test]
+    fn strips_a_single_top_level_folder_from_server_packs() {
+        let nested = vec![
+            "Pack Server/mods/a.jar".to_string(),
+            "Pack Server/server.properties".to_string(),
+        ];
+        let flat = vec!["mods/a.jar".to_string(), "server.properties".to_string()];
+
+        assert_eq!(
+            shared_root_folder(&nested),
+            Some("Pack Server/".to_string())
+        );
+        assert_eq!(shared_root_folder(&flat), None);
+    }
+
+    #[tokio::test]
+    async fn writes_overrides_next_to_different_index_jars() {
+        let root = temp_dir();
+        let output_dir = root.path().join("server");
+        std::fs::create_dir_all(output_dir.join("mods")).unwrap();
+        std::fs::write(output_dir.join("mods/sodium.jar"), "index").unwrap();
+        std::fs::write(output_dir.join("mods/lithium.jar"), "same").unwrap();
+
+        let pack_path = root.path().join("pack.mrpack");
+        write_zip(
+            &pack_path,
+            &[
+                ("modrinth.index.json", "{}"),
+                ("overrides/mods/sodium.jar", "common"),
+                ("server-overrides/mods/sodium.jar", "server"),
+                ("overrides/mods/lithium.jar", "same"),
+            ],
+        );
+
+        extract_overrides(&pack_path, &output_dir, &snapshot_jars(&output_dir))
+            .await
+            .unwrap();
+        let read = |name: &str| std::fs::read_to_string(output_dir.join("mods").join(name)).ok();
+
+        assert_eq!(read("sodium.jar").as_deref(), Some("index"));
+        assert_eq!(read("sodium-overrides.jar").as_deref(), Some("server"));
+        assert_eq!(read("lithium.jar").as_deref(), Some("same"));
+        assert_eq!(read("lithium-overrides.jar"), None);
+    }
+}
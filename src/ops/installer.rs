@@ -1,199 +1,290 @@
+use crate::http::{build_client, download_with_progress};
 use crate::models::{LoaderType, ServerContext};
+use crate::retry::RetryPolicy;
 use crate::ui::{print_success, print_warn, style};
+use crate::utils::{cache_dir, collect_files, sanitize_filename};
 use anyhow::{Context, Result, bail};
-use futures::StreamExt;
-use indicatif::{ProgressBar, ProgressStyle};
-use reqwest::Client;
 use sha1::Sha1;
 use sha2::{Digest, Sha256, Sha512};
-use std::path::Path;
-use tokio::fs::File;
+use std::path::{Path, PathBuf};
+use tokio::fs::{self, File};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-pub async fn install_loader(
+/// Places the loader's server jar or installer in `output_dir`, reusing the cached copy when there is one.
+/// Loader URLs are versioned, so a cached file never goes stale; `offline` only forbids downloading.
+pub async fn fetch_loader_file(
     context: &ServerContext,
     output_dir: &Path,
-    java_path: &str,
-    skip_installer_verify: bool,
-    installer_hash: Option<&str>,
     proxy: Option<&str>,
-) -> Result<String> {
-    let mut client_builder = Client::builder();
+    retry: RetryPolicy,
+    offline: bool,
+) -> Result<()> {
+    let url = loader_file_url(context);
+    let label = loader_label(context);
+    let cached_path = loader_cache_path(&url)?;
+    let target_path = output_dir.join(loader_file_name(context));
 
-    if let Some(proxy_url) = proxy {
-        let proxy =
-            reqwest::Proxy::all(proxy_url).context(format!("Invalid proxy URL: {}", proxy_url))?;
-        client_builder = client_builder.proxy(proxy);
+    if offline && !cached_path.is_file() {
+        bail!(
+            "{} is not available offline: {}\nRun `mcpacker prefetch <pack>` on a connected machine to cache it.",
+            label,
+            url
+        );
+    }
+    if cached_path.is_file() {
+        fs::copy(&cached_path, &target_path)
+            .await
+            .with_context(|| format!("Failed to copy cached {}: {:?}", label, cached_path))?;
+        println!("   Using cached {}: {}", label, style(&url).cyan());
+        return Ok(());
     }
 
-    let client = client_builder.build()?;
+    let client = build_client(proxy)?;
+    let part_path = output_dir.join(format!("{}.part", loader_file_name(context)));
+    download_with_progress(&client, retry, &url, &part_path, label, |_| {}).await?;
+    fs::rename(&part_path, &target_path).await?;
 
+    if let Some(parent) = cached_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let temp_path = cached_path.with_file_name(format!(
+        "{}.part",
+        cached_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+    ));
+    fs::copy(&target_path, &temp_path).await?;
+    fs::rename(&temp_path, &cached_path).await?;
+    Ok(())
+}
+
+/// Sets up the loader from the file `fetch_loader_file` placed in `output_dir`.
+/// `offline` restores the Forge or NeoForge install `cache_server_install` saved, since the
+/// installer downloads libraries.
+pub async fn install_loader(
+    context: &ServerContext,
+    output_dir: &Path,
+    java_path: &str,
+    skip_installer_verify: bool,
+    installer_hash: Option<&str>,
+    offline: bool,
+) -> Result<String> {
     match context.loader_type {
-        LoaderType::Fabric | LoaderType::Quilt => {
-            install_fabric_like(
-                &client,
-                context,
-                output_dir,
-                skip_installer_verify,
-                installer_hash,
-            )
-            .await
-        }
+        LoaderType::Fabric | LoaderType::Quilt => Ok(loader_file_name(context).to_string()),
         LoaderType::Forge | LoaderType::NeoForge => {
-            install_forge_like(
-                &client,
-                context,
-                output_dir,
-                java_path,
-                skip_installer_verify,
+            let installer_path = output_dir.join(loader_file_name(context));
+            check_installer_or_evict(
+                &installer_path,
+                &cached_loader_path(context)?,
                 installer_hash,
+                skip_installer_verify,
             )
-            .await
+            .await?;
+
+            if offline {
+                let install_dir = server_install_cache_path(context)?;
+                if !install_dir.is_dir() {
+                    bail!(offline_install_error(context));
+                }
+                restore_server_install(&install_dir, output_dir)?;
+                println!(
+                    "   Using cached server install: {}",
+                    style(install_dir.display()).cyan()
+                );
+                return Ok(loader_file_name(context).to_string());
+            }
+            install_forge_like(output_dir, java_path).await
         }
     }
 }
 
-async fn download_file_with_progress(
-    client: &Client,
-    url: &str,
-    output_path: &Path,
-    label: &str,
-    expected_hash: Option<&str>,
-    skip_verify: bool,
-    is_installer: bool,
+/// Runs the Forge or NeoForge installer from `output_dir` in a clean directory and keeps what it
+/// wrote, libraries included, for `--offline` runs. Fabric and Quilt need nothing beyond their jar.
+pub async fn cache_server_install(
+    context: &ServerContext,
+    output_dir: &Path,
+    java_path: &str,
+    skip_installer_verify: bool,
+    installer_hash: Option<&str>,
 ) -> Result<()> {
-    let response = client
-        .get(url)
-        .send()
-        .await
-        .context(format!("Failed to request {}", url))?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Download failed for {}: {}", label, response.status());
+    if matches!(context.loader_type, LoaderType::Fabric | LoaderType::Quilt) {
+        return Ok(());
     }
 
-    let total_size = response.content_length().unwrap_or(0);
+    let installer_name = loader_file_name(context);
+    check_installer_or_evict(
+        &output_dir.join(installer_name),
+        &cached_loader_path(context)?,
+        installer_hash,
+        skip_installer_verify,
+    )
+    .await?;
+
+    let install_dir = server_install_cache_path(context)?;
+    let staging = staging_path(&install_dir);
+    let _ = std::fs::remove_dir_all(&staging);
+    std::fs::create_dir_all(&staging)
+        .with_context(|| format!("Failed to create installer directory: {:?}", staging))?;
+    std::fs::copy(
+        output_dir.join(installer_name),
+        staging.join(installer_name),
+    )
+    .context("Failed to copy the installer")?;
 
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(ProgressStyle::with_template(
-        "{spinner:.green} [{elapsed_precise}] {bar:40.cyan/blue} {bytes}/{total_bytes} ({bytes_per_sec}, {eta}) {msg}",
-    )?.progress_chars("━╸ "));
-    pb.set_message(format!("Downloading: {}", label));
+    println!("   Action: Running installer to cache the server install...");
+    let log_path = output_dir.join("installer.log");
+    let installed = run_installer(java_path, installer_name, &staging, &log_path).await;
+    let result = match installed {
+        Ok(()) => save_server_install(&staging, &install_dir, installer_name),
+        Err(e) => Err(e.context(format!("Installer failed; see {}", log_path.display()))),
+    };
+    let _ = std::fs::remove_dir_all(&staging);
+    result?;
 
-    let mut stream = response.bytes_stream();
-    let mut file = File::create(output_path).await?;
+    print_success("Server install cached.");
+    Ok(())
+}
 
-    while let Some(item) = stream.next().await {
-        let chunk = item.context("Failed to read chunk")?;
-        file.write_all(&chunk).await?;
-        pb.inc(chunk.len() as u64);
+/// Fails before anything is downloaded when an `--offline` run could not set up the loader.
+pub fn check_offline_loader(context: &ServerContext) -> Result<()> {
+    let url = loader_file_url(context);
+    if !loader_cache_path(&url)?.is_file() {
+        bail!(
+            "{} is not available offline: {}\nRun `mcpacker prefetch <pack>` on a connected machine to cache it.",
+            loader_label(context),
+            url
+        );
+    }
+    if matches!(
+        context.loader_type,
+        LoaderType::Forge | LoaderType::NeoForge
+    ) && !server_install_cache_path(context)?.is_dir()
+    {
+        bail!(offline_install_error(context));
     }
+    Ok(())
+}
 
-    pb.finish_with_message(format!("{} download complete", label));
+fn offline_install_error(context: &ServerContext) -> String {
+    format!(
+        "No cached {:?} {} server install; its installer downloads libraries and cannot run offline.\nRun `mcpacker prefetch <pack>` on a connected machine with Java to cache it.",
+        context.loader_type, context.loader_version
+    )
+}
 
-    if is_installer {
-        let algorithm = select_hash_algorithm(expected_hash, skip_verify)?;
-        let computed_hash = compute_hash(output_path, algorithm).await?;
-        match expected_hash {
-            Some(expected) if skip_verify => {
-                print_warn("Installer hash verification skipped by flag.");
-                println!(
-                    "   Installer hash (computed): {}",
-                    style(&computed_hash).cyan()
-                );
-                println!("   Installer hash (expected): {}", style(expected).cyan());
-            }
-            Some(expected) => {
-                println!("   Verifying installer hash: {}", style(expected).cyan());
-                verify_installer_hash(&computed_hash, expected)?;
-                print_success("Installer hash verified.");
-            }
-            None => {
-                print_warn("Installer hash not provided; skipping verification.");
-                println!(
-                    "   Installer SHA-256 (computed): {}",
-                    style(&computed_hash).cyan()
-                );
-            }
-        }
+/// Every cached file the loader setup needs: the loader file and any cached server install.
+pub fn cached_loader_files(context: &ServerContext) -> Result<Vec<PathBuf>> {
+    let mut files = vec![cached_loader_path(context)?];
+    collect_files(&server_install_cache_path(context)?, &mut files);
+    Ok(files)
+}
+
+fn loader_file_name(context: &ServerContext) -> &'static str {
+    match context.loader_type {
+        LoaderType::Fabric | LoaderType::Quilt => "server.jar",
+        LoaderType::Forge | LoaderType::NeoForge => "installer.jar",
     }
+}
 
-    Ok(())
+fn loader_label(context: &ServerContext) -> &'static str {
+    match context.loader_type {
+        LoaderType::Fabric | LoaderType::Quilt => "Server Jar",
+        LoaderType::Forge | LoaderType::NeoForge => "Installer",
+    }
 }
 
-async fn install_fabric_like(
-    client: &Client,
-    context: &ServerContext,
-    output_dir: &Path,
-    _skip_installer_verify: bool,
-    _installer_hash: Option<&str>,
-) -> Result<String> {
-    let base_url = if context.loader_type == LoaderType::Quilt {
-        format!(
+fn loader_file_url(context: &ServerContext) -> String {
+    match context.loader_type {
+        LoaderType::Quilt => format!(
             "https://meta.quiltmc.org/v3/versions/loader/{}/{}/server/jar",
             context.minecraft_version, context.loader_version
-        )
-    } else {
-        format!(
+        ),
+        LoaderType::Fabric => format!(
             "https://meta.fabricmc.net/v2/versions/loader/{}/{}/1.0.1/server/jar",
             context.minecraft_version, context.loader_version
-        )
-    };
+        ),
+        LoaderType::NeoForge => format!(
+            "https://maven.neoforged.net/releases/net/neoforged/neoforge/{v}/neoforge-{v}-installer.jar",
+            v = context.loader_version
+        ),
+        LoaderType::Forge => format!(
+            "https://maven.minecraftforge.net/net/minecraftforge/forge/{v}/forge-{v}-installer.jar",
+            v = format!("{}-{}", context.minecraft_version, context.loader_version)
+        ),
+    }
+}
 
-    let jar_path = output_dir.join("server.jar");
-    download_file_with_progress(
-        client,
-        &base_url,
-        &jar_path,
-        "Server Jar",
-        None,
-        false,
-        false,
-    )
-    .await?;
+/// Where `fetch_loader_file` keeps the loader file for `context`.
+pub fn cached_loader_path(context: &ServerContext) -> Result<PathBuf> {
+    loader_cache_path(&loader_file_url(context))
+}
 
-    Ok("server.jar".to_string())
+/// What a finished Forge or NeoForge `--installServer` wrote, kept next to the cached installer.
+fn server_install_cache_path(context: &ServerContext) -> Result<PathBuf> {
+    let installer = cached_loader_path(context)?;
+    let mut name = installer.file_name().unwrap_or_default().to_os_string();
+    name.push(".server");
+    Ok(installer.with_file_name(name))
 }
 
-async fn install_forge_like(
-    client: &Client,
-    context: &ServerContext,
-    output_dir: &Path,
-    java_path: &str,
-    skip_installer_verify: bool,
-    installer_hash: Option<&str>,
-) -> Result<String> {
-    let version_str = if context.loader_type == LoaderType::NeoForge {
-        context.loader_version.to_string()
-    } else {
-        format!("{}-{}", context.minecraft_version, context.loader_version)
-    };
+/// Loader files are cached under a name derived from their URL, which already carries every version.
+fn loader_cache_path(url: &str) -> Result<PathBuf> {
+    let name = url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://");
+    Ok(cache_dir()?.join("loaders").join(sanitize_filename(name)))
+}
 
-    let url = if context.loader_type == LoaderType::NeoForge {
-        format!(
-            "https://maven.neoforged.net/releases/net/neoforged/neoforge/{v}/neoforge-{v}-installer.jar",
-            v = version_str
-        )
-    } else {
-        format!(
-            "https://maven.minecraftforge.net/net/minecraftforge/forge/{v}/forge-{v}-installer.jar",
-            v = version_str
-        )
-    };
+async fn check_installer(
+    installer_path: &Path,
+    expected_hash: Option<&str>,
+    skip_verify: bool,
+) -> Result<()> {
+    let algorithm = select_hash_algorithm(expected_hash, skip_verify)?;
+    let computed_hash = compute_hash(installer_path, algorithm).await?;
+    match expected_hash {
+        Some(expected) if skip_verify => {
+            print_warn("Installer hash verification skipped by flag.");
+            println!(
+                "   Installer hash (computed): {}",
+                style(&computed_hash).cyan()
+            );
+            println!("   Installer hash (expected): {}", style(expected).cyan());
+        }
+        Some(expected) => {
+            println!("   Verifying installer hash: {}", style(expected).cyan());
+            verify_installer_hash(&computed_hash, expected)?;
+            print_success("Installer hash verified.");
+        }
+        None => {
+            print_warn("Installer hash not provided; skipping verification.");
+            println!(
+                "   Installer SHA-256 (computed): {}",
+                style(&computed_hash).cyan()
+            );
+        }
+    }
 
-    let installer_name = "installer.jar";
-    let installer_path = output_dir.join(installer_name);
+    Ok(())
+}
 
-    download_file_with_progress(
-        client,
-        &url,
-        &installer_path,
-        "Installer",
-        installer_hash,
-        skip_installer_verify,
-        true,
-    )
-    .await?;
+/// A rejected installer is also dropped from the cache, so the next run downloads it again.
+async fn check_installer_or_evict(
+    installer_path: &Path,
+    cached_path: &Path,
+    expected_hash: Option<&str>,
+    skip_verify: bool,
+) -> Result<()> {
+    let result = check_installer(installer_path, expected_hash, skip_verify).await;
+    if result.is_err() {
+        let _ = fs::remove_file(cached_path).await;
+    }
+    result
+}
+
+async fn install_forge_like(output_dir: &Path, java_path: &str) -> Result<String> {
+    let installer_name = "installer.jar";
 
     #[cfg(target_os = "windows")]
     let script_name = "install_forge.bat";
@@ -222,7 +313,30 @@ async fn install_forge_like(
     println!("   Action: Running installer automatically...");
 
     let log_path = output_dir.join("installer.log");
-    let log_file = std::fs::File::create(&log_path).context("Failed to create installer.log")?;
+    match run_installer(java_path, installer_name, output_dir, &log_path).await {
+        Ok(()) => print_success("Forge installation successful!"),
+        Err(_) => {
+            print_warn("Automatic installation failed or Java not found.");
+            println!(
+                "      Log: {}",
+                style(log_path.display().to_string()).yellow()
+            );
+            println!("      Please run {} manually.", style(script_name).bold());
+            anyhow::bail!("Installer failed to run successfully");
+        }
+    }
+
+    Ok(installer_name.to_string())
+}
+
+/// Runs `java -jar <installer> --installServer` in `work_dir`, logging to `log_path`.
+async fn run_installer(
+    java_path: &str,
+    installer_name: &str,
+    work_dir: &Path,
+    log_path: &Path,
+) -> Result<()> {
+    let log_file = std::fs::File::create(log_path).context("Failed to create installer.log")?;
     let stdout = std::process::Stdio::from(
         log_file
             .try_clone()
@@ -234,28 +348,69 @@ async fn install_forge_like(
         .arg("-jar")
         .arg(installer_name)
         .arg("--installServer")
-        .current_dir(output_dir)
+        .current_dir(work_dir)
         .stdout(stdout)
         .stderr(stderr)
         .status()
-        .await;
+        .await
+        .context("Failed to run Java")?;
+    if !status.success() {
+        bail!("Installer exited with {}", status);
+    }
+    Ok(())
+}
 
-    match status {
-        Ok(s) if s.success() => {
-            print_success("Forge installation successful!");
-        }
-        _ => {
-            print_warn("Automatic installation failed or Java not found.");
-            println!(
-                "      Log: {}",
-                style(log_path.display().to_string()).yellow()
-            );
-            println!("      Please run {} manually.", style(script_name).bold());
-            anyhow::bail!("Installer failed to run successfully");
+fn staging_path(install_dir: &Path) -> PathBuf {
+    let mut name = install_dir.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".part-{}", std::process::id()));
+    install_dir.with_file_name(name)
+}
+
+/// Replaces the cached install with `staging`, minus the installer itself and its logs.
+fn save_server_install(staging: &Path, install_dir: &Path, installer_name: &str) -> Result<()> {
+    for entry in std::fs::read_dir(staging)
+        .with_context(|| format!("Failed to read {:?}", staging))?
+        .flatten()
+    {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name == installer_name || name.ends_with(".log") {
+            std::fs::remove_file(entry.path())?;
         }
     }
+    let _ = std::fs::remove_dir_all(install_dir);
+    std::fs::rename(staging, install_dir)
+        .with_context(|| format!("Failed to cache server install: {:?}", install_dir))
+}
 
-    Ok(installer_name.to_string())
+/// Copies a server install into `output_dir`; files the pack already put there are kept.
+fn restore_server_install(install_dir: &Path, output_dir: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(install_dir)
+        .with_context(|| format!("Failed to read cached server install: {:?}", install_dir))?
+        .flatten()
+    {
+        copy_recursively(&entry.path(), &output_dir.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+fn copy_recursively(source: &Path, target: &Path) -> Result<()> {
+    if source.is_dir() {
+        std::fs::create_dir_all(target)?;
+        for entry in std::fs::read_dir(source)?.flatten() {
+            copy_recursively(&entry.path(), &target.join(entry.file_name()))?;
+        }
+    } else {
+        if target.exists() {
+            return Ok(());
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(source, target)
+            .with_context(|| format!("Failed to copy {:?} to {:?}", source, target))?;
+    }
+    Ok(())
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -329,8 +484,10 @@ fn verify_installer_hash(computed_hex: &str, expected_hex: &str) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::{
-        HashAlgorithm, detect_hash_algorithm, select_hash_algorithm, verify_installer_hash,
+        HashAlgorithm, check_installer_or_evict, detect_hash_algorithm, restore_server_install,
+        save_server_install, select_hash_algorithm, verify_installer_hash,
     };
+    use crate::test_support::temp_dir;
 
     #[test]
     fn detects_supported_installer_hash_lengths() {
@@ -366,4 +523,56 @@ mod tests {
             HashAlgorithm::Sha256
         );
     }
+
+    #[tokio::test]
+    async fn evicts_a_cached_installer_that_fails_verification() {
+        let root = temp_dir();
+        let installer = root.path().join("installer.jar");
+        let cached = root.path().join("cached-installer.jar");
+        std::fs::write(&installer, "installer").unwrap();
+        std::fs::write(&cached, "installer").unwrap();
+
+        assert!(
+            check_installer_or_evict(&installer, &cached, Some(&"0".repeat(64)), false)
+                .await
+                .is_err()
+        );
+        assert!(!cached.exists());
+    }
+
+    #[test]
+    fn caches_the_whole_install_and_keeps_pack_files_on_restore() {
+        let root = temp_dir();
+        let staging = root.path().join("installer.jar.server.part-1");
+        std::fs::create_dir_all(staging.join("libraries/net/neoforged")).unwrap();
+        std::fs::write(staging.join("libraries/net/neoforged/server.jar"), "lib").unwrap();
+        std::fs::write(staging.join("run.sh"), "java @libraries/args.txt").unwrap();
+        std::fs::write(staging.join("user_jvm_args.txt"), "# defaults").unwrap();
+        std::fs::write(staging.join("installer.jar"), "installer").unwrap();
+        std::fs::write(staging.join("installer.jar.log"), "log").unwrap();
+
+        let install_dir = root.path().join("installer.jar.server");
+        std::fs::create_dir_all(install_dir.join("stale")).unwrap();
+        save_server_install(&staging, &install_dir, "installer.jar").unwrap();
+        assert!(!staging.exists());
+        assert!(!install_dir.join("stale").exists());
+        assert!(install_dir.join("user_jvm_args.txt").is_file());
+        assert!(!install_dir.join("installer.jar").exists());
+        assert!(!install_dir.join("installer.jar.log").exists());
+
+        let output = root.path().join("server");
+        std::fs::create_dir_all(&output).unwrap();
+        std::fs::write(output.join("user_jvm_args.txt"), "-Xmx8G").unwrap();
+        restore_server_install(&install_dir, &output).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(output.join("libraries/net/neoforged/server.jar")).unwrap(),
+            "lib"
+        );
+        assert!(output.join("run.sh").is_file());
+        assert_eq!(
+            std::fs::read_to_string(output.join("user_jvm_args.txt")).unwrap(),
+            "-Xmx8G"
+        );
+    }
 }
@@ -0,0 +1,230 @@
+use crate::models::version::compare_versions;
+use crate::models::{LoaderType, ServerContext};
+use crate::ops::dependencies::{InstalledMod, scan_jars};
+use anyhow::Result;
+use std::fmt;
+use std::path::Path;
+
+/// Mod IDs of loaders that run Fabric mods on Forge or NeoForge (Sinytra Connector).
+const FABRIC_BRIDGE_IDS: [&str; 1] = ["connectormod"];
+
+/// First Minecraft version whose NeoForge no longer reads `META-INF/mods.toml`.
+const NEOFORGE_TOML_CUTOFF: &str = "1.20.5";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compatibility {
+    Compatible,
+    Questionable,
+    Incompatible,
+}
+
+#[derive(Debug)]
+pub struct LoaderMismatch {
+    pub file_name: String,
+    pub loaders: Vec<LoaderType>,
+    pub compatibility: Compatibility,
+    pub server_loader: LoaderType,
+}
+
+impl fmt::Display for LoaderMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let loaders = self
+            .loaders
+            .iter()
+            .map(|loader| format!("{:?}", loader))
+            .collect::<Vec<_>>()
+            .join("/");
+        match self.compatibility {
+            Compatibility::Questionable => write!(
+                f,
+                "{} only has {} metadata and may not load on {:?}",
+                self.file_name, loaders, self.server_loader
+            ),
+            _ => write!(
+                f,
+                "{} is a {} mod and will not load on {:?}",
+                self.file_name, loaders, self.server_loader
+            ),
+        }
+    }
+}
+
+pub async fn check_loaders(
+    output_dir: &Path,
+    context: &ServerContext,
+) -> Result<Vec<LoaderMismatch>> {
+    let mods_dir = output_dir.join("mods");
+    let context = context.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let installed = scan_jars(&mods_dir);
+        let bridge_present = has_fabric_bridge(&installed);
+
+        installed
+            .into_iter()
+            .filter_map(|installed| {
+                let compatibility =
+                    compatibility(&installed.metadata.loaders, &context, bridge_present);
+                (compatibility != Compatibility::Compatible).then(|| LoaderMismatch {
+                    file_name: installed.file_name,
+                    loaders: installed.metadata.loaders,
+                    compatibility,
+                    server_loader: context.loader_type.clone(),
+                })
+            })
+            .collect()
+    })
+    .await
+    .map_err(Into::into)
+}
+
+fn has_fabric_bridge(installed: &[InstalledMod]) -> bool {
+    installed.iter().any(|installed| {
+        installed
+            .metadata
+            .mod_id
+            .as_deref()
+            .is_some_and(|mod_id| FABRIC_BRIDGE_IDS.contains(&mod_id))
+    })
+}
+
+fn compatibility(
+    loaders: &[LoaderType],
+    context: &ServerContext,
+    bridge_present: bool,
+) -> Compatibility {
+    if loaders.is_empty() {
+        return Compatibility::Compatible;
+    }
+
+    let declares = |loader: LoaderType| loaders.contains(&loader);
+    let fabric_like = declares(LoaderType::Fabric) || declares(LoaderType::Quilt);
+
+    let supported = |compatible: bool| {
+        if compatible {
+            Compatibility::Compatible
+        } else {
+            Compatibility::Incompatible
+        }
+    };
+
+    match context.loader_type {
+        LoaderType::Fabric => supported(declares(LoaderType::Fabric)),
+        LoaderType::Quilt => supported(fabric_like),
+        LoaderType::Forge => {
+            supported(declares(LoaderType::Forge) || (fabric_like && bridge_present))
+        }
+        LoaderType::NeoForge
+            if declares(LoaderType::NeoForge) || (fabric_like && bridge_present) =>
+        {
+            Compatibility::Compatible
+        }
+        LoaderType::NeoForge if declares(LoaderType::Forge) => {
+            if compare_versions(&context.minecraft_version, NEOFORGE_TOML_CUTOFF).is_lt() {
+                Compatibility::Compatible
+            } else {
+                Compatibility::Questionable
+            }
+        }
+        LoaderType::NeoForge => Compatibility::Incompatible,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compatibility, compatibility, has_fabric_bridge};
+    use crate::models::{LoaderType, ModMetadata, ServerContext};
+    use crate::ops::dependencies::InstalledMod;
+
+    fn context(loader_type: LoaderType, minecraft_version: &str) -> ServerContext {
+        ServerContext {
+            minecraft_version: minecraft_version.to_string(),
+            loader_type,
+            loader_version: String::new(),
+        }
+    }
+
+    #[test]
+    fn rejects_jars_for_another_loader_family() {
+        let forge = context(LoaderType::Forge, "1.20.1");
+
+        assert_eq!(
+            compatibility(&[LoaderType::Fabric], &forge, false),
+            Compatibility::Incompatible
+        );
+        assert_eq!(
+            compatibility(
+                &[LoaderType::Forge],
+                &context(LoaderType::Fabric, "1.20.1"),
+                false
+            ),
+            Compatibility::Incompatible
+        );
+        assert_eq!(compatibility(&[], &forge, false), Compatibility::Compatible);
+    }
+
+    fn installed(mod_id: &str) -> InstalledMod {
+        InstalledMod {
+            file_name: format!("{}.jar", mod_id),
+            metadata: ModMetadata {
+                mod_id: Some(mod_id.to_string()),
+                ..ModMetadata::default()
+            },
+            nested: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn accepts_fabric_jars_on_quilt_and_with_connector() {
+        assert!(has_fabric_bridge(&[
+            installed("create"),
+            installed("connectormod")
+        ]));
+        assert!(!has_fabric_bridge(&[installed("create")]));
+
+        assert_eq!(
+            compatibility(
+                &[LoaderType::Fabric],
+                &context(LoaderType::Quilt, "1.20.1"),
+                false
+            ),
+            Compatibility::Compatible
+        );
+        assert_eq!(
+            compatibility(
+                &[LoaderType::Quilt],
+                &context(LoaderType::Fabric, "1.20.1"),
+                false
+            ),
+            Compatibility::Incompatible
+        );
+        assert_eq!(
+            compatibility(
+                &[LoaderType::Fabric],
+                &context(LoaderType::NeoForge, "1.21.1"),
+                true
+            ),
+            Compatibility::Compatible
+        );
+    }
+
+    #[test]
+    fn questions_forge_metadata_on_modern_neoforge() {
+        assert_eq!(
+            compatibility(
+                &[LoaderType::Forge],
+                &context(LoaderType::NeoForge, "1.20.1"),
+                false
+            ),
+            Compatibility::Compatible
+        );
+        assert_eq!(
+            compatibility(
+                &[LoaderType::Forge],
+                &context(LoaderType::NeoForge, "1.21.1"),
+                false
+            ),
+            Compatibility::Questionable
+        );
+    }
+}
@@ -1,4 +1,10 @@
+pub mod bundle;
+pub mod client_filter;
+pub mod dependencies;
 pub mod downloader;
+pub mod duplicates;
 pub mod generator;
 pub mod installer;
+pub mod loader_check;
+pub mod mod_cache;
 pub mod utils;
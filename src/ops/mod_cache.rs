@@ -0,0 +1,415 @@
+use crate::utils::cache_dir;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Every cached file is stored once, named by its SHA-512.
+const BLOB_ALGORITHM: &str = "sha512";
+/// Other pack hashes are small index files that hold the SHA-512 of the stored file.
+const INDEX_ALGORITHMS: [&str; 2] = ["sha1", "sha256"];
+/// Maps download URLs to cached files, for packs that do not list file hashes.
+const SOURCES_DIR: &str = "sources";
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub total_bytes: u64,
+    pub oldest: Option<SystemTime>,
+}
+
+/// What a download URL produced the last time it was fetched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceEntry {
+    pub file_name: String,
+    /// SHA-512 of the cached file; `None` when the file was skipped instead of kept.
+    pub sha512: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PruneSummary {
+    pub removed: usize,
+    pub freed_bytes: u64,
+}
+
+pub fn cache_root() -> Result<PathBuf> {
+    Ok(cache_dir()?.join("mod-cache"))
+}
+
+/// Places the cached file for `hash` at `target`; returns `false` on a cache miss.
+pub fn restore(hash_algo: &str, hash: &str, target: &Path) -> Result<bool> {
+    restore_from(&cache_root()?, hash_algo, hash, target)
+}
+
+/// Adds a verified file to the cache and indexes its other hashes, returning the SHA-512.
+pub fn store(path: &Path) -> Result<String> {
+    store_into(&cache_root()?, path)
+}
+
+pub fn record_source(source_url: &str, entry: &SourceEntry) -> Result<()> {
+    record_source_in(&cache_root()?, source_url, entry)
+}
+
+pub fn lookup_source(source_url: &str) -> Option<SourceEntry> {
+    lookup_source_in(&cache_root().ok()?, source_url)
+}
+
+/// Path of the cached file for `hash`, if there is one.
+pub fn cached_file(hash_algo: &str, hash: &str) -> Option<PathBuf> {
+    blob_path(&cache_root().ok()?, hash_algo, hash)
+}
+
+pub fn file_sha512(path: &Path) -> Result<String> {
+    Ok(file_hashes(path)?.sha512)
+}
+
+/// Drops a cache entry whose content no longer matches its hash.
+pub fn evict(hash_algo: &str, hash: &str) {
+    if let Ok(root) = cache_root()
+        && let Some(blob) = blob_path(&root, hash_algo, hash)
+    {
+        let _ = std::fs::remove_file(blob);
+    }
+}
+
+pub fn stats() -> Result<CacheStats> {
+    stats_of(&cache_root()?)
+}
+
+pub fn prune(older_than: Duration) -> Result<PruneSummary> {
+    // An age reaching back before the epoch keeps every file.
+    let cutoff = SystemTime::now()
+        .checked_sub(older_than)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    prune_in(&cache_root()?, cutoff)
+}
+
+fn entry_path(root: &Path, hash_algo: &str, hash: &str) -> Option<PathBuf> {
+    let hash_algo = hash_algo.to_ascii_lowercase();
+    if (hash_algo != BLOB_ALGORITHM && !INDEX_ALGORITHMS.contains(&hash_algo.as_str()))
+        || hash.is_empty()
+        || !hash.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        return None;
+    }
+
+    Some(root.join(hash_algo).join(hash.to_ascii_lowercase()))
+}
+
+/// The stored file for `hash`, following the index for hashes other than SHA-512.
+pub(crate) fn blob_path(root: &Path, hash_algo: &str, hash: &str) -> Option<PathBuf> {
+    let entry = entry_path(root, hash_algo, hash)?;
+    if hash_algo.eq_ignore_ascii_case(BLOB_ALGORITHM) {
+        return entry.is_file().then_some(entry);
+    }
+
+    let sha512 = std::fs::read_to_string(&entry).ok()?;
+    entry_path(root, BLOB_ALGORITHM, sha512.trim()).filter(|blob| blob.is_file())
+}
+
+fn restore_from(root: &Path, hash_algo: &str, hash: &str, target: &Path) -> Result<bool> {
+    let Some(blob) = blob_path(root, hash_algo, hash) else {
+        return Ok(false);
+    };
+
+    if target.exists() {
+        std::fs::remove_file(target)?;
+    }
+    materialize(&blob, target)
+        .with_context(|| format!("Failed to restore cached file: {:?}", target))?;
+
+    // Files age from their last use, so prune keeps what current packs still need.
+    // Index entries live exactly as long as the file they point to.
+    let _ = File::options()
+        .write(true)
+        .open(&blob)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    Ok(true)
+}
+
+pub(crate) fn store_into(root: &Path, path: &Path) -> Result<String> {
+    let hashes = file_hashes(path)?;
+
+    let blob = entry_path(root, BLOB_ALGORITHM, &hashes.sha512).expect("computed hashes are hex");
+    if !blob.is_file() {
+        write_atomically(&blob, |temp| materialize(path, temp))?;
+    }
+    for (hash_algo, hash) in hashes.indexed() {
+        let entry = entry_path(root, hash_algo, hash).expect("computed hashes are hex");
+        if std::fs::read_to_string(&entry).ok().as_deref() != Some(hashes.sha512.as_str()) {
+            write_atomically(&entry, |temp| std::fs::write(temp, &hashes.sha512))?;
+        }
+    }
+
+    Ok(hashes.sha512)
+}
+
+fn write_atomically(entry: &Path, write: impl FnOnce(&Path) -> std::io::Result<()>) -> Result<()> {
+    let parent = entry.parent().expect("entries live in a directory");
+    std::fs::create_dir_all(parent)?;
+    // Each writer gets its own name, so concurrent stores never share a half-written file.
+    let temp = tempfile::NamedTempFile::new_in(parent)?.into_temp_path();
+    // The writer creates the file itself, which lets it reflink.
+    std::fs::remove_file(&temp)?;
+    write(&temp).with_context(|| format!("Failed to write cache entry: {:?}", entry))?;
+    temp.persist(entry)
+        .with_context(|| format!("Failed to write cache entry: {:?}", entry))?;
+    Ok(())
+}
+
+fn source_entry_path(root: &Path, source_url: &str) -> PathBuf {
+    let key = hex::encode(Sha1::digest(source_url.as_bytes()));
+    root.join(SOURCES_DIR).join(format!("{}.json", key))
+}
+
+fn record_source_in(root: &Path, source_url: &str, entry: &SourceEntry) -> Result<()> {
+    let path = source_entry_path(root, source_url);
+    std::fs::create_dir_all(path.parent().expect("entries live in a directory"))?;
+    std::fs::write(&path, serde_json::to_vec(entry)?)
+        .with_context(|| format!("Failed to record cached source: {:?}", path))
+}
+
+fn lookup_source_in(root: &Path, source_url: &str) -> Option<SourceEntry> {
+    let path = source_entry_path(root, source_url);
+    let entry = serde_json::from_slice(&std::fs::read(&path).ok()?).ok()?;
+    let _ = File::options()
+        .write(true)
+        .open(&path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    Some(entry)
+}
+
+/// Reflinks where the filesystem supports it, otherwise copies.
+/// Never hardlinks: a later in-place write to `mods/` would silently change the cached bytes too.
+fn materialize(source: &Path, target: &Path) -> std::io::Result<()> {
+    reflink_copy::reflink_or_copy(source, target).map(|_| ())
+}
+
+struct FileHashes {
+    sha512: String,
+    sha1: String,
+    sha256: String,
+}
+
+impl FileHashes {
+    fn indexed(&self) -> [(&'static str, &str); 2] {
+        [("sha1", &self.sha1), ("sha256", &self.sha256)]
+    }
+}
+
+fn file_hashes(path: &Path) -> Result<FileHashes> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open file for caching: {:?}", path))?;
+    let mut sha512 = Sha512::new();
+    let mut sha1 = Sha1::new();
+    let mut sha256 = Sha256::new();
+    let mut buf = [0u8; 8192];
+
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        sha512.update(&buf[..n]);
+        sha1.update(&buf[..n]);
+        sha256.update(&buf[..n]);
+    }
+
+    Ok(FileHashes {
+        sha512: hex::encode(sha512.finalize()),
+        sha1: hex::encode(sha1.finalize()),
+        sha256: hex::encode(sha256.finalize()),
+    })
+}
+
+fn cached_files(root: &Path, hash_algo: &str) -> Vec<(PathBuf, std::fs::Metadata)> {
+    std::fs::read_dir(root.join(hash_algo))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    let metadata = entry.metadata().ok()?;
+                    metadata.is_file().then(|| (entry.path(), metadata))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn stats_of(root: &Path) -> Result<CacheStats> {
+    let files = cached_files(root, BLOB_ALGORITHM);
+
+    Ok(CacheStats {
+        entries: files.len(),
+        total_bytes: files.iter().map(|(_, metadata)| metadata.len()).sum(),
+        oldest: files
+            .iter()
+            .filter_map(|(_, metadata)| metadata.modified().ok())
+            .min(),
+    })
+}
+
+fn prune_in(root: &Path, cutoff: SystemTime) -> Result<PruneSummary> {
+    let mut summary = PruneSummary::default();
+
+    let remove = |path: &Path| {
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove cached file: {:?}", path))
+    };
+
+    for (path, metadata) in cached_files(root, BLOB_ALGORITHM) {
+        if metadata.modified().is_ok_and(|modified| modified < cutoff) {
+            remove(&path)?;
+            summary.removed += 1;
+            summary.freed_bytes += metadata.len();
+        }
+    }
+    for (path, metadata) in cached_files(root, SOURCES_DIR) {
+        if metadata.modified().is_ok_and(|modified| modified < cutoff) {
+            remove(&path)?;
+        }
+    }
+    // Index entries go with their file.
+    for hash_algo in INDEX_ALGORITHMS {
+        for (path, _) in cached_files(root, hash_algo) {
+            let points_to_blob = path
+                .file_name()
+                .and_then(|hash| blob_path(root, hash_algo, &hash.to_string_lossy()))
+                .is_some();
+            if !points_to_blob {
+                remove(&path)?;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        SourceEntry, blob_path, entry_path, lookup_source_in, prune_in, record_source_in,
+        restore_from, stats_of, store_into,
+    };
+    use crate::test_support::temp_dir;
+    use std::fs::File;
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+
+    const HELLO_SHA1: &str = "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d";
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn only_keys_entries_by_supported_hex_hashes() {
+        let root = Path::new("/cache");
+
+        assert_eq!(
+            entry_path(root, "SHA1", "ABCDEF"),
+            Some(root.join("sha1").join("abcdef"))
+        );
+        assert_eq!(
+            entry_path(root, "sha256", "abcdef"),
+            Some(root.join("sha256").join("abcdef"))
+        );
+        assert_eq!(entry_path(root, "md5", "abcdef"), None);
+        assert_eq!(entry_path(root, "sha1", "../escape"), None);
+        assert_eq!(entry_path(root, "sha1", ""), None);
+    }
+
+    #[test]
+    fn stores_restores_and_prunes_files() {
+        let root = temp_dir();
+        let root = root.path();
+        let cache = root.join("cache");
+        let source = root.join("hello.jar");
+        std::fs::write(&source, "hello").unwrap();
+
+        let sha512 = store_into(&cache, &source).unwrap();
+        let target = root.join("mods").join("hello.jar");
+        std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+
+        assert!(restore_from(&cache, "sha1", HELLO_SHA1, &target).unwrap());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "hello");
+        assert!(!restore_from(&cache, "sha1", "00", &target).unwrap());
+
+        let stats = stats_of(&cache).unwrap();
+        assert_eq!((stats.entries, stats.total_bytes), (1, 5));
+
+        // Restored files must not share storage with the cache entry.
+        std::fs::write(&target, "bye").unwrap();
+        assert!(restore_from(&cache, "sha1", HELLO_SHA1, &root.join("again.jar")).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(root.join("again.jar")).unwrap(),
+            "hello"
+        );
+
+        let url = "https://www.curseforge.com/api/v1/mods/1/files/2/download";
+        let entry = SourceEntry {
+            file_name: "hello.jar".to_string(),
+            sha512: Some(sha512),
+        };
+        assert_eq!(lookup_source_in(&cache, url), None);
+        record_source_in(&cache, url, &entry).unwrap();
+        assert_eq!(lookup_source_in(&cache, url), Some(entry));
+
+        let kept = prune_in(&cache, SystemTime::now() - Duration::from_secs(3600)).unwrap();
+        assert_eq!(kept.removed, 0);
+        let pruned = prune_in(&cache, SystemTime::now() + Duration::from_secs(3600)).unwrap();
+        assert_eq!((pruned.removed, pruned.freed_bytes), (1, 5));
+        assert!(!restore_from(&cache, "sha1", HELLO_SHA1, &target).unwrap());
+        assert!(!cache.join("sha1").join(HELLO_SHA1).exists());
+        assert_eq!(lookup_source_in(&cache, url), None);
+    }
+
+    #[test]
+    fn keeps_index_entries_alive_with_the_file_they_point_to() {
+        let root = temp_dir();
+        let cache = root.path().join("cache");
+        let source = root.path().join("hello.jar");
+        std::fs::write(&source, "hello").unwrap();
+        let sha512 = store_into(&cache, &source).unwrap();
+
+        // A restore by SHA-512 alone keeps the SHA-1 lookup working through a prune.
+        let cutoff = SystemTime::now() + Duration::from_secs(60);
+        let old = cutoff - Duration::from_secs(3600);
+        for entry in [
+            cache.join("sha512").join(&sha512),
+            cache.join("sha1").join(HELLO_SHA1),
+        ] {
+            File::options()
+                .write(true)
+                .open(entry)
+                .unwrap()
+                .set_modified(old)
+                .unwrap();
+        }
+        let target = root.path().join("restored.jar");
+        assert!(restore_from(&cache, "sha512", &sha512, &target).unwrap());
+        let pruned = prune_in(&cache, cutoff - Duration::from_secs(120)).unwrap();
+
+        assert_eq!(pruned.removed, 0);
+        assert!(restore_from(&cache, "sha1", HELLO_SHA1, &target).unwrap());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "hello");
+    }
+
+    #[test]
+    fn finds_files_by_their_sha256() {
+        let root = temp_dir();
+        let cache = root.path().join("cache");
+        let source = root.path().join("hello.jar");
+        std::fs::write(&source, "hello").unwrap();
+        let sha512 = store_into(&cache, &source).unwrap();
+
+        assert_eq!(
+            blob_path(&cache, "SHA256", HELLO_SHA256),
+            Some(cache.join("sha512").join(sha512))
+        );
+        let target = root.path().join("restored.jar");
+        assert!(restore_from(&cache, "sha256", HELLO_SHA256, &target).unwrap());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "hello");
+    }
+}
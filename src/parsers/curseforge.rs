@@ -1,6 +1,10 @@
+use crate::http::with_proxy;
 use crate::models::{LoaderType, ModInfo, ServerContext, SideType};
-use crate::parsers::filter;
+use crate::parsers::rules::{FilterRules, RuleAction, RuleTarget};
+use crate::parsers::source;
+use crate::retry::RetryPolicy;
 use crate::ui::print_warn;
+use crate::utils::{BROWSER_USER_AGENT, cache_dir};
 use anyhow::{Context, Result, bail};
 use futures::StreamExt;
 use reqwest::{
@@ -10,12 +14,9 @@ use reqwest::{
 };
 use serde::Deserialize;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
-use zip::ZipArchive;
 
 #[derive(Debug, Deserialize)]
 struct Manifest {
@@ -58,34 +59,69 @@ struct CfFileData {
     file_name: String,
 }
 
-const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+/// Resolved file names, keyed by "project/file", so offline runs can still filter by name.
+const FILE_NAME_CACHE: &str = "curseforge-file-names.json";
 
 pub async fn parse_curseforge(
     path: &Path,
     filter_client: bool,
     proxy: Option<&str>,
+    retry: RetryPolicy,
+    offline: bool,
+    rules: &FilterRules,
 ) -> Result<(ServerContext, Vec<ModInfo>)> {
-    let file = File::open(path).with_context(|| format!("Failed to open file: {:?}", path))?;
-    let mut archive = ZipArchive::new(file).with_context(|| "Failed to open zip archive")?;
+    let manifest = read_manifest(path)?;
+    let server_context = server_context_from_minecraft(&manifest.minecraft)?;
 
-    let mut manifest_file = archive
-        .by_name("manifest.json")
-        .with_context(|| "manifest.json not found in archive")?;
+    let mut mods = Vec::new();
+    let resolution = if filter_client || !rules.is_empty() {
+        resolve_cf_file_names(&manifest.files, proxy, retry, offline).await?
+    } else {
+        ResolutionState::default()
+    };
+
+    if let Some(message) = partial_filter_warning(filter_client, resolution.failed) {
+        print_warn(&message);
+    }
+
+    for file in manifest.files {
+        let resolved_file_name = resolution
+            .file_names
+            .get(&(file.project_id, file.file_id))
+            .map(String::as_str);
 
-    let mut json_content = String::new();
-    manifest_file.read_to_string(&mut json_content)?;
+        let project_id = file.project_id.to_string();
+        let target = RuleTarget {
+            project_id: Some(&project_id),
+            file_name: resolved_file_name,
+            ..RuleTarget::default()
+        };
+        if rules.action(&target) == Some(RuleAction::Remove) {
+            continue;
+        }
 
-    let manifest: Manifest =
-        serde_json::from_str(&json_content).with_context(|| "Failed to parse manifest.json")?;
+        mods.push(build_mod_info(&file, resolved_file_name));
+    }
 
-    let mc_version = manifest.minecraft.version;
+    Ok((server_context, mods))
+}
 
-    let primary_loader = manifest
-        .minecraft
+pub fn read_server_context(path: &Path) -> Result<ServerContext> {
+    server_context_from_minecraft(&read_manifest(path)?.minecraft)
+}
+
+fn read_manifest(path: &Path) -> Result<Manifest> {
+    let json_content = source::open(path)?.read_to_string("manifest.json")?;
+
+    serde_json::from_str(&json_content).with_context(|| "Failed to parse manifest.json")
+}
+
+fn server_context_from_minecraft(minecraft: &MinecraftInfo) -> Result<ServerContext> {
+    let primary_loader = minecraft
         .mod_loaders
         .iter()
         .find(|ml| ml.primary)
-        .or_else(|| manifest.minecraft.mod_loaders.first());
+        .or_else(|| minecraft.mod_loaders.first());
 
     let (loader_type, loader_version) = if let Some(loader) = primary_loader {
         if loader.id.starts_with("forge-") {
@@ -115,48 +151,11 @@ pub async fn parse_curseforge(
         bail!("No mod loader found in manifest");
     };
 
-    let server_context = ServerContext {
-        minecraft_version: mc_version,
+    Ok(ServerContext {
+        minecraft_version: minecraft.version.clone(),
         loader_type,
         loader_version,
-    };
-
-    let mut mods = Vec::new();
-    let keywords = if filter_client {
-        filter::client_only_keywords()
-    } else {
-        Vec::new()
-    };
-
-    if filter_client && keywords.is_empty() {
-        print_warn("Client-only filter list is empty; CurseForge filtering may be incomplete.");
-    }
-
-    let resolution = if filter_client && !keywords.is_empty() {
-        resolve_cf_file_names(&manifest.files, proxy).await?
-    } else {
-        ResolutionState::default()
-    };
-
-    if let Some(message) = partial_filter_warning(filter_client, resolution.failed) {
-        print_warn(&message);
-    }
-
-    for file in manifest.files {
-        let resolved_file_name = resolution
-            .file_names
-            .get(&(file.project_id, file.file_id))
-            .map(String::as_str);
-
-        let Some(mod_info) = build_mod_info(&file, resolved_file_name, &keywords, filter_client)
-        else {
-            continue;
-        };
-
-        mods.push(mod_info);
-    }
-
-    Ok((server_context, mods))
+    })
 }
 
 #[derive(Default)]
@@ -168,7 +167,34 @@ struct ResolutionState {
 async fn resolve_cf_file_names(
     files: &[ManifestFile],
     proxy: Option<&str>,
+    retry: RetryPolicy,
+    offline: bool,
 ) -> Result<ResolutionState> {
+    let cache_path = file_name_cache_path()?;
+    let mut cached = load_file_name_cache(&cache_path);
+
+    let file_names = if offline {
+        HashMap::new()
+    } else {
+        fetch_cf_file_names(files, proxy, retry).await?
+    };
+    if !file_names.is_empty() {
+        for ((project_id, file_id), file_name) in &file_names {
+            cached.insert(file_name_key(*project_id, *file_id), file_name.clone());
+        }
+        if let Err(e) = save_file_name_cache(&cache_path, &cached) {
+            print_warn(&format!("Failed to cache CurseForge file names: {:#}", e));
+        }
+    }
+
+    Ok(fill_from_cache(files, file_names, &cached))
+}
+
+async fn fetch_cf_file_names(
+    files: &[ManifestFile],
+    proxy: Option<&str>,
+    retry: RetryPolicy,
+) -> Result<HashMap<(u32, u32), String>> {
     let client = build_metadata_client(proxy)?;
     let _ = client.get("https://www.curseforge.com").send().await;
 
@@ -176,7 +202,7 @@ async fn resolve_cf_file_names(
         let client = client.clone();
         async move {
             Ok::<_, anyhow::Error>(
-                fetch_cf_file_name(&client, file.project_id, file.file_id)
+                fetch_cf_file_name(&client, retry, file.project_id, file.file_id)
                     .await
                     .map(|file_name| ((file.project_id, file.file_id), file_name)),
             )
@@ -186,20 +212,59 @@ async fn resolve_cf_file_names(
     .collect::<Vec<_>>()
     .await;
 
-    let mut state = ResolutionState {
-        file_names: HashMap::with_capacity(files.len()),
-        failed: 0,
-    };
+    let mut file_names = HashMap::with_capacity(files.len());
     for entry in resolved {
-        match entry? {
-            Ok(((project_id, file_id), file_name)) => {
-                state.file_names.insert((project_id, file_id), file_name);
+        if let Ok((key, file_name)) = entry? {
+            file_names.insert(key, file_name);
+        }
+    }
+
+    Ok(file_names)
+}
+
+/// Completes freshly resolved names with cached ones; files found in neither count as failed.
+fn fill_from_cache(
+    files: &[ManifestFile],
+    mut file_names: HashMap<(u32, u32), String>,
+    cached: &HashMap<String, String>,
+) -> ResolutionState {
+    let mut failed = 0;
+    for file in files {
+        let key = (file.project_id, file.file_id);
+        if file_names.contains_key(&key) {
+            continue;
+        }
+        match cached.get(&file_name_key(file.project_id, file.file_id)) {
+            Some(file_name) => {
+                file_names.insert(key, file_name.clone());
             }
-            Err(_) => state.failed += 1,
+            None => failed += 1,
         }
     }
 
-    Ok(state)
+    ResolutionState { file_names, failed }
+}
+
+fn file_name_key(project_id: u32, file_id: u32) -> String {
+    format!("{}/{}", project_id, file_id)
+}
+
+pub fn file_name_cache_path() -> Result<PathBuf> {
+    Ok(cache_dir()?.join(FILE_NAME_CACHE))
+}
+
+fn load_file_name_cache(path: &Path) -> HashMap<String, String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_file_name_cache(path: &Path, cached: &HashMap<String, String>) -> Result<()> {
+    let temp_path = path.with_extension("json.tmp");
+    std::fs::write(&temp_path, serde_json::to_string(cached)?)
+        .with_context(|| format!("Failed to write {:?}", temp_path))?;
+    std::fs::rename(&temp_path, path).with_context(|| format!("Failed to write {:?}", path))
 }
 
 fn build_metadata_client(proxy: Option<&str>) -> Result<Client> {
@@ -215,10 +280,10 @@ fn build_metadata_client(proxy: Option<&str>) -> Result<Client> {
         HeaderValue::from_static("https://www.curseforge.com"),
     );
 
-    let mut client_builder = Client::builder()
+    let client_builder = Client::builder()
         .redirect(reqwest::redirect::Policy::limited(10))
         .cookie_provider(jar)
-        .user_agent(USER_AGENT)
+        .user_agent(BROWSER_USER_AGENT)
         .tcp_nodelay(true)
         .pool_max_idle_per_host(10)
         .pool_idle_timeout(Duration::from_secs(15))
@@ -226,25 +291,24 @@ fn build_metadata_client(proxy: Option<&str>) -> Result<Client> {
         .timeout(Duration::from_secs(20))
         .default_headers(headers);
 
-    if let Some(proxy_url) = proxy {
-        let proxy = reqwest::Proxy::all(proxy_url)
-            .with_context(|| format!("Invalid proxy URL: {}", proxy_url))?;
-        client_builder = client_builder.proxy(proxy);
-    }
-
-    client_builder.build().map_err(Into::into)
+    with_proxy(client_builder, proxy)?
+        .build()
+        .map_err(Into::into)
 }
 
-async fn fetch_cf_file_name(client: &Client, project_id: u32, file_id: u32) -> Result<String> {
+async fn fetch_cf_file_name(
+    client: &Client,
+    retry: RetryPolicy,
+    project_id: u32,
+    file_id: u32,
+) -> Result<String> {
     let meta_url = format!(
         "https://www.curseforge.com/api/v1/mods/{}/files/{}",
         project_id, file_id
     );
 
-    let response = client
-        .get(&meta_url)
-        .header("Accept", "application/json")
-        .send()
+    let response = retry
+        .send(client.get(&meta_url).header("Accept", "application/json"))
         .await
         .with_context(|| {
             format!(
@@ -272,39 +336,24 @@ async fn fetch_cf_file_name(client: &Client, project_id: u32, file_id: u32) -> R
     Ok(json.data.file_name)
 }
 
-fn build_mod_info(
-    file: &ManifestFile,
-    resolved_file_name: Option<&str>,
-    keywords: &[String],
-    filter_client: bool,
-) -> Option<ModInfo> {
+fn build_mod_info(file: &ManifestFile, resolved_file_name: Option<&str>) -> ModInfo {
     let (default_name, temp_file_name, download_urls) =
         resolve_cf_file_multi_mirror(file.project_id, file.file_id);
     let file_name = resolved_file_name.unwrap_or(&temp_file_name).to_string();
     let display_name = resolved_file_name
         .map(display_name_from_file_name)
         .unwrap_or(default_name);
-    let side = if filter_client
-        && resolved_file_name.is_some_and(|name| filter::is_client_only_name(name, keywords))
-    {
-        SideType::Client
-    } else {
-        SideType::Both
-    };
-
-    if side == SideType::Client {
-        return None;
-    }
 
-    Some(ModInfo {
+    ModInfo {
         name: display_name,
         file_name,
         download_urls,
         hash: String::new(),
         hash_algo: "none".to_string(),
-        side,
+        side: SideType::Both,
         is_required: file.required,
-    })
+        install_path: None,
+    }
 }
 
 fn display_name_from_file_name(file_name: &str) -> String {
@@ -342,8 +391,10 @@ fn resolve_cf_file_multi_mirror(project_id: u32, file_id: u32) -> (String, Strin
 #[cfg(test)]
 mod tests {
     use super::{
-        ManifestFile, build_mod_info, display_name_from_file_name, partial_filter_warning,
+        ManifestFile, build_mod_info, display_name_from_file_name, fill_from_cache,
+        partial_filter_warning,
     };
+    use std::collections::HashMap;
 
     #[test]
     fn strips_extension_from_resolved_file_name() {
@@ -354,40 +405,54 @@ mod tests {
     }
 
     #[test]
-    fn filters_client_only_curseforge_mods_when_name_is_resolved() {
+    fn keeps_keyword_matches_for_the_post_download_check() {
         let file = ManifestFile {
             project_id: 394468,
             file_id: 1234567,
             required: true,
         };
-        let keywords = vec!["sodium".to_string()];
-
-        assert!(
-            build_mod_info(
-                &file,
-                Some("Sodium-Fabric-0.5.0+mc1.20.1.jar"),
-                &keywords,
-                true
-            )
-            .is_none()
-        );
+
+        let mod_info = build_mod_info(&file, Some("Sodium-Fabric-0.5.0+mc1.20.1.jar"));
+
+        assert_eq!(mod_info.name, "Sodium-Fabric-0.5.0+mc1.20.1");
+        assert_eq!(mod_info.file_name, "Sodium-Fabric-0.5.0+mc1.20.1.jar");
     }
 
     #[test]
-    fn keeps_unresolved_entries_even_when_filtering_is_enabled() {
+    fn falls_back_to_file_id_names_when_unresolved() {
         let file = ManifestFile {
             project_id: 394468,
             file_id: 1234567,
             required: true,
         };
-        let keywords = vec!["sodium".to_string()];
 
-        let mod_info = build_mod_info(&file, None, &keywords, true).unwrap();
+        let mod_info = build_mod_info(&file, None);
 
         assert_eq!(mod_info.name, "CF-394468");
         assert_eq!(mod_info.file_name, "1234567.jar");
     }
 
+    #[test]
+    fn fills_unresolved_names_from_the_cache() {
+        let files = [(1, 10), (2, 20), (3, 30)].map(|(project_id, file_id)| ManifestFile {
+            project_id,
+            file_id,
+            required: true,
+        });
+        let fetched = HashMap::from([((1, 10), "fresh.jar".to_string())]);
+        let cached = HashMap::from([
+            ("1/10".to_string(), "stale.jar".to_string()),
+            ("2/20".to_string(), "cached.jar".to_string()),
+        ]);
+
+        let state = fill_from_cache(&files, fetched, &cached);
+
+        assert_eq!(state.file_names[&(1, 10)], "fresh.jar");
+        assert_eq!(state.file_names[&(2, 20)], "cached.jar");
+        assert!(!state.file_names.contains_key(&(3, 30)));
+        assert_eq!(state.failed, 1);
+    }
+
     #[test]
     fn reports_partial_filter_failures() {
         assert!(partial_filter_warning(true, 2).is_some());
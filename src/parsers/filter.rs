@@ -1,52 +1,314 @@
-use anyhow::{Context, Result};
+use crate::http::with_proxy;
+use crate::retry::RetryPolicy;
+use crate::ui::{print_step, print_warn};
+use crate::utils::{cache_dir, sanitize_filename};
+use anyhow::{Context, Result, bail};
+use chrono::{NaiveDateTime, TimeDelta};
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 const FALLBACK_URL: &str = "https://raw.githubusercontent.com/Griefed/ServerPackCreator/main/serverpackcreator-api/src/main/resources/serverpackcreator.properties";
+const DEFAULT_SOURCE_NAME: &str = "serverpackcreator";
+const UPDATED_HEADER: &str = "# Updated: ";
+const UPDATED_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+/// Single ServerPackCreator list cache written by versions before `filter_lists/`.
+const LEGACY_CACHE_FILE: &str = "fallback_mods.txt";
 
-fn get_cache_path() -> Result<PathBuf> {
-    let project_dirs = directories::ProjectDirs::from("com", "mcpacker", "mcpacker")
-        .context("Failed to determine cache directory")?;
-    let cache_dir = project_dirs.cache_dir();
-    if !cache_dir.exists() {
-        std::fs::create_dir_all(cache_dir)?;
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ListFormat {
+    /// ServerPackCreator `.properties` with a `fallbackmodslist` entry
+    Properties,
+    /// One keyword per line, `#` starts a comment
+    #[default]
+    Text,
+    /// JSON array of mod IDs
+    Json,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListSource {
+    name: String,
+    #[serde(default)]
+    format: ListFormat,
+    url: Option<String>,
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SourcesFile {
+    sources: Vec<ListSource>,
+}
+
+/// A client-only keyword and the names of the lists that contain it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keyword {
+    pub value: String,
+    pub sources: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum KeywordMatch {
+    /// Match keywords against the `-`, `_`, `+`, `.` separated words of a name
+    Token,
+    /// Treat each keyword as a case-insensitive regular expression
+    Regex,
+}
+
+#[derive(Debug)]
+enum Pattern {
+    Tokens(Vec<String>),
+    Regex(Regex),
+}
+
+#[derive(Debug)]
+pub struct KeywordMatcher {
+    keywords: Vec<(Keyword, Pattern)>,
+}
+
+#[derive(Debug)]
+pub struct SourceUpdate {
+    pub name: String,
+    pub result: Result<usize>,
+}
+
+pub fn sources_config_path() -> Result<PathBuf> {
+    Ok(cache_dir()?.join("filter_sources.toml"))
+}
+
+fn get_cache_path(source_name: &str) -> Result<PathBuf> {
+    let dir = cache_dir()?.join("filter_lists");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join(format!("{}.txt", sanitize_filename(source_name))))
+}
+
+fn default_sources() -> Vec<ListSource> {
+    vec![ListSource {
+        name: DEFAULT_SOURCE_NAME.to_string(),
+        format: ListFormat::Properties,
+        url: Some(FALLBACK_URL.to_string()),
+        path: None,
+    }]
+}
+
+fn configured_sources() -> Result<Vec<ListSource>> {
+    let path = sources_config_path()?;
+    if !path.exists() {
+        return Ok(default_sources());
+    }
+
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read filter sources: {:?}", path))?;
+    let config: SourcesFile = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse filter sources: {:?}", path))?;
+    validated_sources(config.sources)
+}
+
+fn validated_sources(sources: Vec<ListSource>) -> Result<Vec<ListSource>> {
+    if sources.is_empty() {
+        bail!("No client-only list sources configured");
+    }
+
+    let mut names = HashSet::new();
+    for source in &sources {
+        if !names.insert(sanitize_filename(&source.name)) {
+            bail!("Duplicate client-only list source name: {}", source.name);
+        }
+        if source.url.is_some() == source.path.is_some() {
+            bail!(
+                "Client-only list source '{}' needs exactly one of `url` or `path`",
+                source.name
+            );
+        }
+    }
+
+    Ok(sources)
+}
+
+/// Moves a legacy `fallback_mods.txt` into `filter_lists/` as the ServerPackCreator cache,
+/// or deletes it when that source is not configured or already has a newer cache.
+pub fn migrate_legacy_cache() -> Result<()> {
+    let legacy = cache_dir()?.join(LEGACY_CACHE_FILE);
+    if !legacy.is_file() {
+        return Ok(());
+    }
+
+    let target = configured_sources()?
+        .iter()
+        .any(|source| source.name == DEFAULT_SOURCE_NAME)
+        .then(|| get_cache_path(DEFAULT_SOURCE_NAME))
+        .transpose()?;
+    if move_legacy_cache(&legacy, target.as_deref())? {
+        print_step("Moved the old client-only mods list cache into filter_lists/");
+    } else {
+        print_step("Removed the old client-only mods list cache (fallback_mods.txt)");
+    }
+    Ok(())
+}
+
+fn move_legacy_cache(legacy: &Path, target: Option<&Path>) -> Result<bool> {
+    match target {
+        Some(target) if !target.exists() => {
+            std::fs::rename(legacy, target)
+                .with_context(|| format!("Failed to move {:?} to {:?}", legacy, target))?;
+            Ok(true)
+        }
+        _ => {
+            std::fs::remove_file(legacy)
+                .with_context(|| format!("Failed to remove {:?}", legacy))?;
+            Ok(false)
+        }
     }
-    Ok(cache_dir.join("fallback_mods.txt"))
 }
 
 pub fn is_cache_present() -> bool {
-    get_cache_path().map(|p| p.exists()).unwrap_or(false)
+    configured_sources().is_ok_and(|sources| {
+        sources.iter().any(|source| {
+            get_cache_path(&source.name)
+                .map(|p| p.exists())
+                .unwrap_or(false)
+        })
+    })
+}
+
+/// Cached list files of the configured sources that exist on disk.
+pub fn cached_list_paths() -> Vec<PathBuf> {
+    configured_sources()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|source| get_cache_path(&source.name).ok())
+        .filter(|path| path.is_file())
+        .collect()
 }
 
-pub async fn update_fallback_list(proxy: Option<&str>) -> Result<()> {
-    let mut client_builder = reqwest::Client::builder();
+/// Age of the oldest source cache, or `None` when a configured source has no dated cache.
+pub fn cache_age() -> Option<TimeDelta> {
+    let now = chrono::Local::now().naive_local();
+    configured_sources()
+        .ok()?
+        .iter()
+        .map(|source| {
+            let content = std::fs::read_to_string(get_cache_path(&source.name).ok()?).ok()?;
+            parse_updated(&content).map(|updated| now - updated)
+        })
+        .try_fold(TimeDelta::zero(), |oldest, age| Some(oldest.max(age?)))
+}
+
+fn parse_updated(content: &str) -> Option<NaiveDateTime> {
+    let timestamp = content.lines().next()?.strip_prefix(UPDATED_HEADER)?;
+    NaiveDateTime::parse_from_str(timestamp.trim(), UPDATED_FORMAT).ok()
+}
 
-    if let Some(proxy_url) = proxy {
-        let proxy = reqwest::Proxy::all(proxy_url)
-            .with_context(|| format!("Invalid proxy URL: {}", proxy_url))?;
-        client_builder = client_builder.proxy(proxy);
+pub fn format_age(age: TimeDelta) -> String {
+    if age.num_hours() < 1 {
+        format!("{} minutes", age.num_minutes().max(0))
+    } else if age.num_days() < 2 {
+        format!("{} hours", age.num_hours())
+    } else {
+        format!("{} days", age.num_days())
     }
+}
 
-    let client = client_builder.build()?;
-    let response = client
-        .get(FALLBACK_URL)
-        .send()
-        .await?
-        .error_for_status()?
-        .text()
-        .await?;
-    let keywords = validated_keywords(parse_keywords_from_properties(&response))?;
-    let cache_path = get_cache_path()?;
-    let mut file = File::create(cache_path)?;
+/// Refreshes every configured source; a failing source keeps its previous cache.
+pub async fn update_fallback_list(
+    proxy: Option<&str>,
+    retry: RetryPolicy,
+    offline: bool,
+) -> Result<Vec<SourceUpdate>> {
+    let sources = configured_sources()?;
 
-    let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
-    writeln!(file, "# Updated: {}", now)?;
+    let client = with_proxy(reqwest::Client::builder(), proxy)?.build()?;
 
+    let mut updates = Vec::new();
+    for source in &sources {
+        let result = update_source(&client, retry, source, offline).await;
+        if let Err(e) = &result {
+            print_warn(&format!(
+                "Failed to update client-only list '{}': {:#}",
+                source.name, e
+            ));
+        }
+        updates.push(SourceUpdate {
+            name: source.name.clone(),
+            result,
+        });
+    }
+
+    if updates.iter().all(|update| update.result.is_err()) {
+        bail!("Failed to update any client-only mods list");
+    }
+    Ok(updates)
+}
+
+async fn update_source(
+    client: &reqwest::Client,
+    retry: RetryPolicy,
+    source: &ListSource,
+    offline: bool,
+) -> Result<usize> {
+    let content = match (&source.url, &source.path) {
+        (Some(_), _) if offline => bail!("Remote list is not available in offline mode"),
+        (Some(url), _) => {
+            retry
+                .send(client.get(url))
+                .await?
+                .error_for_status()?
+                .text()
+                .await?
+        }
+        (None, Some(path)) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read client-only list: {:?}", path))?,
+        (None, None) => bail!("Client-only list source has no location"),
+    };
+
+    let keywords = validated_keywords(parse_keywords(source.format, &content)?)?;
+    let cache_path = get_cache_path(&source.name)?;
+
+    // Written beside the cache and renamed over it, so a failed or concurrent run keeps the old list.
+    let temp_path = cache_path.with_extension(format!("txt.{}.tmp", std::process::id()));
+    let result = write_cached_list(&temp_path, &keywords)
+        .and_then(|()| std::fs::rename(&temp_path, &cache_path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result.with_context(|| format!("Failed to cache client-only list: {:?}", cache_path))?;
+    Ok(keywords.len())
+}
+
+fn write_cached_list(path: &Path, keywords: &[String]) -> std::io::Result<()> {
+    let mut file = File::create(path)?;
+    let now = chrono::Local::now().format(UPDATED_FORMAT);
+    writeln!(file, "{}{}", UPDATED_HEADER, now)?;
     for kw in keywords {
         writeln!(file, "{}", kw)?;
     }
-    Ok(())
+    file.sync_all()
+}
+
+fn parse_keywords(format: ListFormat, content: &str) -> Result<Vec<String>> {
+    let keywords = match format {
+        ListFormat::Properties => parse_keywords_from_properties(content),
+        ListFormat::Text => content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect(),
+        ListFormat::Json => serde_json::from_str::<Vec<String>>(content)
+            .context("Expected a JSON array of mod IDs")?
+            .iter()
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .collect(),
+    };
+
+    Ok(keywords)
 }
 
 fn parse_keywords_from_properties(content: &str) -> Vec<String> {
@@ -88,8 +350,8 @@ fn parse_keywords_from_properties(content: &str) -> Vec<String> {
     keywords
 }
 
-pub(crate) fn client_only_keywords() -> Vec<String> {
-    if let Ok(path) = get_cache_path()
+fn read_cached_keywords(source_name: &str) -> Vec<String> {
+    if let Ok(path) = get_cache_path(source_name)
         && path.exists()
         && let Ok(content) = std::fs::read_to_string(path)
     {
@@ -103,14 +365,100 @@ pub(crate) fn client_only_keywords() -> Vec<String> {
     Vec::new()
 }
 
-pub(crate) fn is_client_only_name(name: &str, keywords: &[String]) -> bool {
-    let name_lower = name.to_lowercase();
-    keywords.iter().any(|keyword| name_lower.contains(keyword))
+pub(crate) fn client_only_keywords() -> Vec<Keyword> {
+    let Ok(sources) = configured_sources() else {
+        return Vec::new();
+    };
+
+    merge_keywords(
+        sources
+            .iter()
+            .map(|source| (source.name.as_str(), read_cached_keywords(&source.name))),
+    )
+}
+
+fn merge_keywords<'a>(lists: impl Iterator<Item = (&'a str, Vec<String>)>) -> Vec<Keyword> {
+    let mut merged: BTreeMap<String, Keyword> = BTreeMap::new();
+    for (source, keywords) in lists {
+        for value in keywords {
+            let keyword = merged
+                .entry(value.to_lowercase())
+                .or_insert_with(|| Keyword {
+                    value,
+                    sources: Vec::new(),
+                });
+            if !keyword.sources.iter().any(|name| name == source) {
+                keyword.sources.push(source.to_string());
+            }
+        }
+    }
+
+    merged.into_values().collect()
+}
+
+impl KeywordMatcher {
+    pub fn new(keywords: Vec<Keyword>, mode: KeywordMatch) -> Result<Self> {
+        let keywords = keywords
+            .into_iter()
+            .map(|keyword| {
+                let pattern = match mode {
+                    KeywordMatch::Token => Pattern::Tokens(tokenize(&keyword.value)),
+                    KeywordMatch::Regex => Pattern::Regex(
+                        RegexBuilder::new(&keyword.value)
+                            .case_insensitive(true)
+                            .build()
+                            .with_context(|| {
+                                format!(
+                                    "Invalid keyword regex '{}' from {}",
+                                    keyword.value,
+                                    keyword.sources.join(", ")
+                                )
+                            })?,
+                    ),
+                };
+                Ok((keyword, pattern))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { keywords })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keywords.is_empty()
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Keyword> {
+        let tokens = tokenize(name.strip_suffix(".jar").unwrap_or(name));
+        self.keywords
+            .iter()
+            .find(|(_, pattern)| match pattern {
+                Pattern::Tokens(keyword) => matches_tokens(keyword, &tokens),
+                Pattern::Regex(regex) => regex.is_match(name),
+            })
+            .map(|(keyword, _)| keyword)
+    }
+}
+
+fn tokenize(name: &str) -> Vec<String> {
+    name.to_lowercase()
+        .split(['-', '_', '+', '.'])
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// A one-word keyword must equal a whole token; longer keywords must match the leading tokens.
+fn matches_tokens(keyword: &[String], name: &[String]) -> bool {
+    match keyword {
+        [] => false,
+        [word] => name.contains(word),
+        _ => name.starts_with(keyword),
+    }
 }
 
 fn validated_keywords(keywords: Vec<String>) -> Result<Vec<String>> {
     if keywords.is_empty() {
-        anyhow::bail!("Failed to parse any client-only mod keywords from fallback list")
+        bail!("Failed to parse any client-only mod keywords from the list")
     }
 
     Ok(keywords)
@@ -118,7 +466,28 @@ fn validated_keywords(keywords: Vec<String>) -> Result<Vec<String>> {
 
 #[cfg(test)]
 mod tests {
-    use super::{is_client_only_name, parse_keywords_from_properties, validated_keywords};
+    use super::{
+        Keyword, KeywordMatch, KeywordMatcher, ListFormat, ListSource, format_age, merge_keywords,
+        move_legacy_cache, parse_keywords, parse_keywords_from_properties, parse_updated,
+        validated_keywords, validated_sources,
+    };
+    use crate::test_support::temp_dir;
+    use chrono::{NaiveDate, TimeDelta};
+
+    fn matcher(values: &[&str], mode: KeywordMatch) -> KeywordMatcher {
+        let keywords = values
+            .iter()
+            .map(|value| Keyword {
+                value: value.to_string(),
+                sources: vec!["test".to_string()],
+            })
+            .collect();
+        KeywordMatcher::new(keywords, mode).unwrap()
+    }
+
+    fn matched<'a>(matcher: &'a KeywordMatcher, name: &str) -> Option<&'a str> {
+        matcher.find(name).map(|keyword| keyword.value.as_str())
+    }
 
     #[test]
     fn parses_multiline_keyword_properties() {
@@ -139,14 +508,119 @@ entityculling
     }
 
     #[test]
-    fn matches_keywords_case_insensitively() {
-        let keywords = vec!["sodium".to_string(), "iris".to_string()];
+    fn parses_text_and_json_lists() {
+        assert_eq!(
+            parse_keywords(ListFormat::Text, "# client mods\nSodium\n\n  iris \n").unwrap(),
+            vec!["Sodium".to_string(), "iris".to_string()]
+        );
+        assert_eq!(
+            parse_keywords(ListFormat::Json, r#"["Sodium", "", "iris"]"#).unwrap(),
+            vec!["Sodium".to_string(), "iris".to_string()]
+        );
+        assert!(parse_keywords(ListFormat::Json, r#"{"mods": []}"#).is_err());
+    }
+
+    #[test]
+    fn merges_lists_and_records_provenance() {
+        let merged = merge_keywords(
+            [
+                ("spc", vec!["sodium".to_string(), "iris".to_string()]),
+                ("local", vec!["Sodium".to_string(), "sodium".to_string()]),
+            ]
+            .into_iter(),
+        );
+
+        assert_eq!(
+            merged,
+            vec![
+                Keyword {
+                    value: "iris".to_string(),
+                    sources: vec!["spc".to_string()],
+                },
+                Keyword {
+                    value: "sodium".to_string(),
+                    sources: vec!["spc".to_string(), "local".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_ambiguous_or_duplicate_sources() {
+        let source = |name: &str, url: Option<&str>, path: Option<&str>| ListSource {
+            name: name.to_string(),
+            format: ListFormat::Text,
+            url: url.map(str::to_string),
+            path: path.map(Into::into),
+        };
+
+        assert!(validated_sources(vec![source("a", Some("https://x"), Some("a.txt"))]).is_err());
+        assert!(validated_sources(vec![source("a", None, None)]).is_err());
+        assert!(
+            validated_sources(vec![
+                source("a", Some("https://x"), None),
+                source("a", None, Some("a.txt")),
+            ])
+            .is_err()
+        );
+        assert!(validated_sources(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn matches_keywords_as_whole_tokens() {
+        let matcher = matcher(&["sodium", "iris", "ok"], KeywordMatch::Token);
+
+        assert_eq!(
+            matched(&matcher, "Sodium-Fabric-0.5.0+mc1.20.1.jar"),
+            Some("sodium")
+        );
+        assert_eq!(matched(&matcher, "iris_shaders-1.6.jar"), Some("iris"));
+        assert_eq!(matched(&matcher, "irisflowers-server.jar"), None);
+        assert_eq!(matched(&matcher, "lookout-1.0.jar"), None);
+        assert_eq!(matched(&matcher, "lithium-fabric-0.12.0.jar"), None);
+    }
+
+    #[test]
+    fn matches_multi_word_keywords_as_name_prefixes() {
+        let matcher = matcher(&["textrues_embeddium_options-"], KeywordMatch::Token);
 
-        assert!(is_client_only_name(
-            "Sodium-Fabric-0.5.0+mc1.20.1.jar",
-            &keywords
-        ));
-        assert!(!is_client_only_name("lithium-fabric-0.12.0.jar", &keywords));
+        assert!(matched(&matcher, "textrues_embeddium_options-1.0.jar").is_some());
+        assert!(matched(&matcher, "addon-textrues_embeddium_options.jar").is_none());
+    }
+
+    #[test]
+    fn matches_regex_keywords() {
+        let matcher = matcher(&["^iris(shaders)?-"], KeywordMatch::Regex);
+
+        assert!(matched(&matcher, "Iris-1.6.4.jar").is_some());
+        assert!(matched(&matcher, "irisflowers-server.jar").is_none());
+        assert!(
+            KeywordMatcher::new(
+                vec![Keyword {
+                    value: "(".to_string(),
+                    sources: vec!["test".to_string()],
+                }],
+                KeywordMatch::Regex
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn reads_the_updated_header() {
+        assert_eq!(
+            parse_updated("# Updated: 2024-03-01 08:30:00\nsodium\n"),
+            NaiveDate::from_ymd_opt(2024, 3, 1).and_then(|date| date.and_hms_opt(8, 30, 0))
+        );
+        assert_eq!(parse_updated("sodium\n"), None);
+        assert_eq!(parse_updated("# Updated: yesterday\n"), None);
+    }
+
+    #[test]
+    fn formats_cache_ages() {
+        assert_eq!(format_age(TimeDelta::minutes(5)), "5 minutes");
+        assert_eq!(format_age(TimeDelta::hours(30)), "30 hours");
+        assert_eq!(format_age(TimeDelta::days(9)), "9 days");
     }
 
     #[test]
@@ -154,4 +628,33 @@ entityculling
         assert!(validated_keywords(Vec::new()).is_err());
         assert!(validated_keywords(vec!["sodium".to_string()]).is_ok());
     }
+
+    #[test]
+    fn moves_the_legacy_cache_unless_a_newer_one_exists() {
+        let root = temp_dir();
+        let legacy = root.path().join("fallback_mods.txt");
+        let target = root.path().join("serverpackcreator.txt");
+
+        std::fs::write(&legacy, "# Updated: 2024-01-01 00:00:00\nsodium\n").unwrap();
+        assert!(move_legacy_cache(&legacy, Some(&target)).unwrap());
+        assert!(!legacy.exists());
+        assert!(
+            std::fs::read_to_string(&target)
+                .unwrap()
+                .ends_with("sodium\n")
+        );
+
+        std::fs::write(&legacy, "oculus\n").unwrap();
+        assert!(!move_legacy_cache(&legacy, Some(&target)).unwrap());
+        assert!(!legacy.exists());
+        assert!(
+            std::fs::read_to_string(&target)
+                .unwrap()
+                .ends_with("sodium\n")
+        );
+
+        std::fs::write(&legacy, "oculus\n").unwrap();
+        assert!(!move_legacy_cache(&legacy, None).unwrap());
+        assert!(!legacy.exists());
+    }
 }
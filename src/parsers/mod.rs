@@ -1,3 +1,106 @@
 pub mod curseforge;
 pub mod filter;
 pub mod modrinth;
+pub mod packwiz;
+pub mod prism;
+pub mod rules;
+pub mod source;
+
+use anyhow::{Result, bail};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackFormat {
+    Modrinth,
+    CurseForge,
+    Prism,
+    Packwiz,
+}
+
+pub fn detect_format(path: &Path) -> Result<PackFormat> {
+    if packwiz::is_packwiz_pack(path) {
+        return Ok(PackFormat::Packwiz);
+    }
+
+    let names = source::open(path)?.file_names();
+    if let Some(format) = format_from_names(&names) {
+        return Ok(format);
+    }
+
+    bail!(
+        "Unrecognized modpack {:?}: expected modrinth.index.json, manifest.json, mmc-pack.json or pack.toml, found: {}",
+        path,
+        describe_top_level(&names)
+    )
+}
+
+fn format_from_names(names: &[String]) -> Option<PackFormat> {
+    let contains = |wanted: &str| names.iter().any(|name| name == wanted);
+
+    if contains("modrinth.index.json") {
+        Some(PackFormat::Modrinth)
+    } else if prism::find_instance_root(names.iter().map(String::as_str)).is_some() {
+        Some(PackFormat::Prism)
+    } else if contains("manifest.json") {
+        Some(PackFormat::CurseForge)
+    } else {
+        None
+    }
+}
+
+fn describe_top_level(names: &[String]) -> String {
+    let mut entries: Vec<&str> = names
+        .iter()
+        .map(|name| match name.find('/') {
+            Some(index) => &name[..=index],
+            None => name.as_str(),
+        })
+        .collect();
+    entries.sort_unstable();
+    entries.dedup();
+
+    if entries.is_empty() {
+        "an empty archive".to_string()
+    } else {
+        entries.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PackFormat, describe_top_level, format_from_names};
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn detects_format_from_manifest_regardless_of_extension() {
+        assert_eq!(
+            format_from_names(&names(&["modrinth.index.json", "overrides/a.txt"])),
+            Some(PackFormat::Modrinth)
+        );
+        assert_eq!(
+            format_from_names(&names(&["manifest.json", "overrides/a.txt"])),
+            Some(PackFormat::CurseForge)
+        );
+        assert_eq!(
+            format_from_names(&names(&["Pack/mmc-pack.json", "Pack/instance.cfg"])),
+            Some(PackFormat::Prism)
+        );
+        assert_eq!(format_from_names(&names(&["Pack/manifest.json"])), None);
+    }
+
+    #[test]
+    fn lists_top_level_entries_of_unrecognized_packs() {
+        assert_eq!(
+            describe_top_level(&names(&[
+                "Pack/manifest.json",
+                "Pack/mods/a.jar",
+                "readme.txt"
+            ])),
+            "Pack/, readme.txt"
+        );
+        assert_eq!(describe_top_level(&[]), "an empty archive");
+    }
+}
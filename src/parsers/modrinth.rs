@@ -1,10 +1,19 @@
 use crate::models::{LoaderType, ModInfo, ServerContext, SideType};
+use crate::parsers::rules::{FilterRules, RuleAction, RuleTarget, project_id_from_url};
+use crate::parsers::source;
+use crate::ui::print_warn;
+use crate::utils::is_safe_relative_path;
 use anyhow::{Context, Result, bail};
 use serde::Deserialize;
-use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
-use zip::ZipArchive;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+const TRUSTED_DOWNLOAD_HOSTS: [&str; 4] = [
+    "cdn.modrinth.com",
+    "github.com",
+    "raw.githubusercontent.com",
+    "gitlab.com",
+];
 
 #[derive(Debug, Deserialize)]
 struct MrPackIndex {
@@ -32,16 +41,13 @@ struct MrPackEnv {
     server: Option<String>,
 }
 
-pub fn parse_mrpack(path: &PathBuf, keep_client: bool) -> Result<(ServerContext, Vec<ModInfo>)> {
-    let file = File::open(path).with_context(|| format!("Failed to open file: {:?}", path))?;
-    let mut archive = ZipArchive::new(file).with_context(|| "Failed to open zip archive")?;
-
-    let mut index_file = archive
-        .by_name("modrinth.index.json")
-        .with_context(|| "modrinth.index.json not found in archive")?;
-
-    let mut json_content = String::new();
-    index_file.read_to_string(&mut json_content)?;
+pub fn parse_mrpack(
+    path: &Path,
+    keep_client: bool,
+    allow_untrusted_hosts: bool,
+    rules: &FilterRules,
+) -> Result<(ServerContext, Vec<ModInfo>)> {
+    let json_content = source::open(path)?.read_to_string("modrinth.index.json")?;
 
     let index: MrPackIndex = serde_json::from_str(&json_content)
         .with_context(|| "Failed to parse modrinth.index.json")?;
@@ -74,65 +80,219 @@ pub fn parse_mrpack(path: &PathBuf, keep_client: bool) -> Result<(ServerContext,
     };
 
     let mut mods = Vec::new();
+    let mut untrusted = Vec::new();
 
     for file in index.files {
-        let client_env = file
-            .env
-            .as_ref()
-            .and_then(|e| e.client.as_deref())
-            .unwrap_or("required");
-        let server_env = file
-            .env
-            .as_ref()
-            .and_then(|e| e.server.as_deref())
-            .unwrap_or("required");
-
-        let side = match (client_env, server_env) {
-            (_, "unsupported") => SideType::Client,
-            ("unsupported", _) => SideType::Server,
-            _ => SideType::Both,
-        };
-
-        if side == SideType::Client && !keep_client {
-            continue;
+        untrusted.extend(
+            file.downloads
+                .iter()
+                .filter(|url| !is_trusted_download_url(url))
+                .cloned(),
+        );
+        if let Some(mod_info) = build_mod_info(file, keep_client, rules)? {
+            mods.push(mod_info);
         }
+    }
 
-        let is_required = server_env == "required";
-
-        let file_path_in_pack = PathBuf::from(&file.path);
-        let file_name = file_path_in_pack
-            .file_name()
-            .map(|s| s.to_string_lossy().into_owned())
-            .unwrap_or_else(|| "unknown.jar".to_string());
-
-        let name = file_path_in_pack
-            .file_stem()
-            .map(|s| s.to_string_lossy().into_owned())
-            .unwrap_or_else(|| file_name.clone());
-
-        let (hash, hash_algo) = if let Some(h) = file.hashes.sha512 {
-            (h, "sha512".to_string())
-        } else if let Some(h) = file.hashes.sha1 {
-            (h, "sha1".to_string())
-        } else {
-            ("".to_string(), "none".to_string())
-        };
-
-        let download_urls = file.downloads.clone();
-        if download_urls.is_empty() {
-            continue;
+    if !untrusted.is_empty() {
+        if !allow_untrusted_hosts {
+            bail!(
+                "modrinth.index.json references {} download URLs outside the trusted hosts ({}):\n  {}\nUse --allow-untrusted-hosts to accept them anyway.",
+                untrusted.len(),
+                TRUSTED_DOWNLOAD_HOSTS.join(", "),
+                untrusted.join("\n  ")
+            );
         }
+        print_warn(&format!(
+            "Accepting {} download URLs from untrusted hosts (--allow-untrusted-hosts).",
+            untrusted.len()
+        ));
+    }
+
+    Ok((server_context, mods))
+}
+
+fn is_trusted_download_url(url: &str) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+
+    url.scheme() == "https"
+        && url
+            .host_str()
+            .is_some_and(|host| TRUSTED_DOWNLOAD_HOSTS.contains(&host))
+}
+
+fn build_mod_info(
+    file: MrPackFile,
+    keep_client: bool,
+    rules: &FilterRules,
+) -> Result<Option<ModInfo>> {
+    let client_env = file
+        .env
+        .as_ref()
+        .and_then(|e| e.client.as_deref())
+        .unwrap_or("required");
+    let server_env = file
+        .env
+        .as_ref()
+        .and_then(|e| e.server.as_deref())
+        .unwrap_or("required");
+
+    let side = match (client_env, server_env) {
+        (_, "unsupported") => SideType::Client,
+        ("unsupported", _) => SideType::Server,
+        _ => SideType::Both,
+    };
+
+    let is_required = server_env == "required";
+
+    let file_path_in_pack = PathBuf::from(&file.path);
+    if !is_safe_relative_path(&file_path_in_pack) {
+        bail!("Unsafe file path in modrinth.index.json: {}", file.path);
+    }
+
+    let file_name = file_path_in_pack
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "unknown.jar".to_string());
+
+    let project_id = file
+        .downloads
+        .iter()
+        .find_map(|url| project_id_from_url(url));
+    let target = RuleTarget {
+        project_id: project_id.as_deref(),
+        file_name: Some(&file_name),
+        hashes: [&file.hashes.sha1, &file.hashes.sha512]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect(),
+        ..RuleTarget::default()
+    };
+    match rules.action(&target) {
+        Some(RuleAction::Remove) => return Ok(None),
+        Some(RuleAction::Keep) => {}
+        None if side == SideType::Client && !keep_client => return Ok(None),
+        None => {}
+    }
+
+    let name = file_path_in_pack
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| file_name.clone());
 
-        mods.push(ModInfo {
-            name,
-            file_name,
-            download_urls,
-            hash,
-            hash_algo,
-            side,
-            is_required,
+    let (hash, hash_algo) = if let Some(h) = file.hashes.sha512 {
+        (h, "sha512".to_string())
+    } else if let Some(h) = file.hashes.sha1 {
+        (h, "sha1".to_string())
+    } else {
+        ("".to_string(), "none".to_string())
+    };
+
+    let download_urls = file.downloads;
+    if download_urls.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(ModInfo {
+        name,
+        file_name,
+        download_urls,
+        hash,
+        hash_algo,
+        side,
+        is_required,
+        install_path: Some(file_path_in_pack),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MrPackEnv, MrPackFile, MrPackHashes, build_mod_info, is_trusted_download_url};
+    use crate::parsers::rules::FilterRules;
+    use std::path::PathBuf;
+
+    fn index_file(path: &str) -> MrPackFile {
+        MrPackFile {
+            path: path.to_string(),
+            hashes: MrPackHashes {
+                sha1: Some("deadbeef".to_string()),
+                sha512: None,
+            },
+            env: None,
+            downloads: vec![format!("https://cdn.modrinth.com/data/abc/{}", path)],
+        }
+    }
+
+    #[test]
+    fn keeps_declared_install_path() {
+        let mod_info = build_mod_info(
+            index_file("resourcepacks/Faithful.zip"),
+            false,
+            &FilterRules::default(),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(mod_info.file_name, "Faithful.zip");
+        assert_eq!(
+            mod_info.install_path,
+            Some(PathBuf::from("resourcepacks/Faithful.zip"))
+        );
+    }
+
+    #[test]
+    fn rules_override_the_declared_environment() {
+        let rules = FilterRules::from_toml(&[
+            "[force_keep]\nproject_ids = [\"abc\"]\n[force_remove]\nhashes = [\"deadbeef\"]",
+        ]);
+        let mut client_only = index_file("mods/iris.jar");
+        client_only.env = Some(MrPackEnv {
+            client: Some("required".to_string()),
+            server: Some("unsupported".to_string()),
         });
+
+        assert!(
+            build_mod_info(client_only, false, &rules)
+                .unwrap()
+                .is_some()
+        );
+
+        let mut other = index_file("mods/lithium.jar");
+        other.downloads = vec!["https://cdn.modrinth.com/data/xyz/lithium.jar".to_string()];
+        assert!(build_mod_info(other, false, &rules).unwrap().is_none());
     }
 
-    Ok((server_context, mods))
+    #[test]
+    fn rejects_index_paths_that_escape_the_server_directory() {
+        assert!(
+            build_mod_info(
+                index_file("../mods/evil.jar"),
+                false,
+                &FilterRules::default()
+            )
+            .is_err()
+        );
+        assert!(build_mod_info(index_file("/etc/passwd"), false, &FilterRules::default()).is_err());
+    }
+
+    #[test]
+    fn trusts_only_allowlisted_https_hosts() {
+        assert!(is_trusted_download_url(
+            "https://cdn.modrinth.com/data/AANobbMI/versions/abc/sodium.jar"
+        ));
+        assert!(is_trusted_download_url(
+            "https://github.com/owner/repo/releases/download/v1/mod.jar"
+        ));
+        assert!(!is_trusted_download_url(
+            "http://cdn.modrinth.com/data/mod.jar"
+        ));
+        assert!(!is_trusted_download_url(
+            "https://cdn.modrinth.com.evil.example/mod.jar"
+        ));
+        assert!(!is_trusted_download_url("https://example.com/mod.jar"));
+        assert!(!is_trusted_download_url("not a url"));
+    }
 }
@@ -0,0 +1,503 @@
+use crate::http::with_proxy;
+use crate::models::{LoaderType, ModInfo, ServerContext, SideType};
+use crate::retry::RetryPolicy;
+use crate::ui::print_warn;
+use crate::utils::{cache_dir, is_safe_relative_path, sanitize_filename};
+use anyhow::{Context, Result, bail};
+use futures::StreamExt;
+use md5::Md5;
+use reqwest::Client;
+use serde::Deserialize;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use url::Url;
+
+#[derive(Debug, Deserialize)]
+struct PackToml {
+    name: String,
+    index: PackIndexRef,
+    versions: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PackIndexRef {
+    file: String,
+    #[serde(rename = "hash-format")]
+    hash_format: String,
+    hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexToml {
+    #[serde(rename = "hash-format")]
+    hash_format: String,
+    #[serde(default)]
+    files: Vec<IndexFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexFile {
+    file: String,
+    #[serde(default)]
+    hash: String,
+    #[serde(rename = "hash-format")]
+    hash_format: Option<String>,
+    #[serde(default)]
+    metafile: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetaFile {
+    name: String,
+    filename: String,
+    side: Option<String>,
+    download: MetaDownload,
+    update: Option<MetaUpdate>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetaDownload {
+    url: Option<String>,
+    #[serde(rename = "hash-format")]
+    hash_format: String,
+    hash: String,
+    mode: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetaUpdate {
+    curseforge: Option<MetaCurseForge>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetaCurseForge {
+    #[serde(rename = "project-id")]
+    project_id: u32,
+    #[serde(rename = "file-id")]
+    file_id: u32,
+}
+
+pub fn is_packwiz_pack(path: &Path) -> bool {
+    if path.is_dir() {
+        path.join("pack.toml").is_file()
+    } else {
+        path.extension().is_some_and(|ext| ext == "toml")
+    }
+}
+
+fn pack_toml_path(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        path.join("pack.toml")
+    } else {
+        path.to_path_buf()
+    }
+}
+
+fn read_pack(path: &Path) -> Result<(PackToml, PathBuf, IndexToml)> {
+    let pack_path = pack_toml_path(path);
+    let content = std::fs::read_to_string(&pack_path)
+        .with_context(|| format!("Failed to read {:?}", pack_path))?;
+    let pack: PackToml = toml::from_str(&content).with_context(|| "Failed to parse pack.toml")?;
+
+    let index_relative = safe_relative_path(&pack.index.file)?;
+    let index_path = pack_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(index_relative);
+    let content = std::fs::read_to_string(&index_path)
+        .with_context(|| format!("Failed to read {:?}", index_path))?;
+    let index: IndexToml =
+        toml::from_str(&content).with_context(|| "Failed to parse packwiz index")?;
+
+    let index_dir = index_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .to_path_buf();
+    Ok((pack, index_dir, index))
+}
+
+pub fn parse_packwiz(path: &Path, keep_client: bool) -> Result<(ServerContext, Vec<ModInfo>)> {
+    let (pack, index_dir, index) = read_pack(path)?;
+    let server_context = server_context_from_versions(&pack.versions)?;
+
+    let mut mods = Vec::new();
+    for entry in index.files.iter().filter(|entry| is_metafile(entry)) {
+        let relative = safe_relative_path(&entry.file)?;
+        let meta_path = index_dir.join(&relative);
+        let content = std::fs::read_to_string(&meta_path)
+            .with_context(|| format!("Failed to read metafile {:?}", meta_path))?;
+        let meta: MetaFile = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse metafile {}", entry.file))?;
+
+        if let Some(mod_info) = build_mod_info(&relative, meta, keep_client)? {
+            mods.push(mod_info);
+        }
+    }
+
+    Ok((server_context, mods))
+}
+
+pub fn override_files(path: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let (_, index_dir, index) = read_pack(path)?;
+
+    index
+        .files
+        .iter()
+        .filter(|entry| !is_metafile(entry))
+        .map(|entry| {
+            let relative = safe_relative_path(&entry.file)?;
+            Ok((index_dir.join(&relative), relative))
+        })
+        .collect()
+}
+
+fn is_metafile(entry: &IndexFile) -> bool {
+    entry.metafile || entry.file.ends_with(".pw.toml")
+}
+
+fn safe_relative_path(path: &str) -> Result<PathBuf> {
+    let relative = PathBuf::from(path);
+    if !is_safe_relative_path(&relative) {
+        bail!("Unsafe file path in packwiz pack: {}", path);
+    }
+    Ok(relative)
+}
+
+fn server_context_from_versions(versions: &HashMap<String, String>) -> Result<ServerContext> {
+    let minecraft_version = versions
+        .get("minecraft")
+        .with_context(|| "Minecraft version not found in pack.toml")?
+        .clone();
+
+    let (loader_type, loader_version) = if let Some(v) = versions.get("fabric") {
+        (LoaderType::Fabric, v.clone())
+    } else if let Some(v) = versions.get("forge") {
+        (LoaderType::Forge, v.clone())
+    } else if let Some(v) = versions.get("neoforge") {
+        (LoaderType::NeoForge, v.clone())
+    } else if let Some(v) = versions.get("quilt") {
+        (LoaderType::Quilt, v.clone())
+    } else {
+        bail!("Unsupported or missing loader in pack.toml: {:?}", versions);
+    };
+
+    Ok(ServerContext {
+        minecraft_version,
+        loader_type,
+        loader_version,
+    })
+}
+
+fn build_mod_info(
+    metafile_path: &Path,
+    meta: MetaFile,
+    keep_client: bool,
+) -> Result<Option<ModInfo>> {
+    let side = match meta.side.as_deref() {
+        Some("client") => SideType::Client,
+        Some("server") => SideType::Server,
+        _ => SideType::Both,
+    };
+
+    if side == SideType::Client && !keep_client {
+        return Ok(None);
+    }
+
+    let install_path = metafile_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(&meta.filename);
+    if !is_safe_relative_path(&install_path) {
+        bail!("Unsafe file name in packwiz metafile: {}", meta.filename);
+    }
+
+    let download_urls = if let Some(url) = meta.download.url {
+        vec![url]
+    } else if meta.download.mode.as_deref() == Some("metadata:curseforge")
+        && let Some(cf) = meta.update.and_then(|update| update.curseforge)
+    {
+        vec![format!(
+            "https://www.curseforge.com/api/v1/mods/{}/files/{}/download",
+            cf.project_id, cf.file_id
+        )]
+    } else {
+        print_warn(&format!(
+            "Skipping {}: metafile has no usable download source.",
+            meta.name
+        ));
+        return Ok(None);
+    };
+
+    let (hash, hash_algo) = match meta.download.hash_format.to_ascii_lowercase().as_str() {
+        algo @ ("sha1" | "sha256" | "sha512") => (meta.download.hash, algo.to_string()),
+        _ => (String::new(), "none".to_string()),
+    };
+
+    Ok(Some(ModInfo {
+        name: meta.name,
+        file_name: meta.filename,
+        download_urls,
+        hash,
+        hash_algo,
+        side,
+        is_required: true,
+        install_path: Some(install_path),
+    }))
+}
+
+pub async fn fetch_remote_pack(
+    url: &str,
+    proxy: Option<&str>,
+    retry: RetryPolicy,
+    parallel: usize,
+) -> Result<PathBuf> {
+    let client = with_proxy(Client::builder(), proxy)?.build()?;
+    let pack_url = Url::parse(url).with_context(|| format!("Invalid pack URL: {}", url))?;
+
+    let pack_bytes = fetch_bytes(&client, retry, &pack_url).await?;
+    let pack: PackToml = toml::from_str(std::str::from_utf8(&pack_bytes)?)
+        .with_context(|| "Failed to parse pack.toml")?;
+
+    // Keyed by URL so packs sharing a name do not overwrite each other's files.
+    let pack_dir = cache_dir()?
+        .join("packwiz")
+        .join(&hex::encode(Sha1::digest(url.as_bytes()))[..12])
+        .join(sanitize_filename(&pack.name));
+    fs::create_dir_all(&pack_dir).await?;
+    fs::write(pack_dir.join("pack.toml"), &pack_bytes).await?;
+
+    let index_relative = safe_relative_path(&pack.index.file)?;
+    let index_url = pack_url.join(&pack.index.file)?;
+    let index_bytes = fetch_bytes(&client, retry, &index_url).await?;
+    if !hash_matches(&index_bytes, &pack.index.hash_format, &pack.index.hash)? {
+        bail!("Hash mismatch for packwiz index {}", index_url);
+    }
+    let index_path = pack_dir.join(&index_relative);
+    if let Some(parent) = index_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(&index_path, &index_bytes).await?;
+
+    let index: IndexToml = toml::from_str(std::str::from_utf8(&index_bytes)?)
+        .with_context(|| "Failed to parse packwiz index")?;
+    let index_dir = index_path.parent().unwrap_or(&pack_dir).to_path_buf();
+
+    let results = futures::stream::iter(index.files.iter().map(|entry| {
+        let client = client.clone();
+        let index_url = index_url.clone();
+        let index_dir = index_dir.clone();
+        let hash_format = entry
+            .hash_format
+            .clone()
+            .unwrap_or_else(|| index.hash_format.clone());
+        async move {
+            let relative = safe_relative_path(&entry.file)?;
+            let dest = index_dir.join(&relative);
+            if let Ok(existing) = fs::read(&dest).await
+                && hash_matches(&existing, &hash_format, &entry.hash)?
+            {
+                return Ok(());
+            }
+
+            let file_url = index_url.join(&entry.file)?;
+            let bytes = fetch_bytes(&client, retry, &file_url).await?;
+            if !hash_matches(&bytes, &hash_format, &entry.hash)? {
+                bail!("Hash mismatch for packwiz file {}", file_url);
+            }
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::write(&dest, &bytes).await?;
+            Ok::<(), anyhow::Error>(())
+        }
+    }))
+    .buffer_unordered(parallel)
+    .collect::<Vec<_>>()
+    .await;
+
+    for result in results {
+        result?;
+    }
+
+    Ok(pack_dir)
+}
+
+async fn fetch_bytes(client: &Client, retry: RetryPolicy, url: &Url) -> Result<Vec<u8>> {
+    let response = retry
+        .send(client.get(url.as_str()))
+        .await
+        .with_context(|| format!("Failed to request {}", url))?;
+
+    if !response.status().is_success() {
+        bail!("Download failed for {}: {}", url, response.status());
+    }
+
+    Ok(response.bytes().await?.to_vec())
+}
+
+fn hash_matches(bytes: &[u8], hash_format: &str, expected: &str) -> Result<bool> {
+    let computed = match hash_format.to_ascii_lowercase().as_str() {
+        "sha1" => hex::encode(Sha1::digest(bytes)),
+        "sha256" => hex::encode(Sha256::digest(bytes)),
+        "sha512" => hex::encode(Sha512::digest(bytes)),
+        "md5" => hex::encode(Md5::digest(bytes)),
+        "murmur2" => return Ok(expected.trim().parse::<u32>().ok() == Some(murmur2(bytes))),
+        other => bail!("Unsupported packwiz hash format: {}", other),
+    };
+    Ok(computed.eq_ignore_ascii_case(expected))
+}
+
+/// The CurseForge fingerprint packwiz calls `murmur2`: MurmurHash2 with seed 1 over the bytes
+/// left after dropping tabs, line breaks and spaces, written as a decimal number.
+fn murmur2(bytes: &[u8]) -> u32 {
+    const M: u32 = 0x5bd1_e995;
+    let data: Vec<u8> = bytes
+        .iter()
+        .copied()
+        .filter(|byte| !matches!(byte, 9 | 10 | 13 | 32))
+        .collect();
+
+    let mut hash = 1 ^ data.len() as u32;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> 24;
+        k = k.wrapping_mul(M);
+        hash = hash.wrapping_mul(M) ^ k;
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (index, byte) in tail.iter().enumerate() {
+            hash ^= u32::from(*byte) << (8 * index);
+        }
+        hash = hash.wrapping_mul(M);
+    }
+
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(M);
+    hash ^ (hash >> 15)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetaFile, build_mod_info, hash_matches, murmur2, server_context_from_versions};
+    use crate::models::{LoaderType, SideType};
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+
+    fn metafile(content: &str) -> MetaFile {
+        toml::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn reads_loader_from_pack_versions() {
+        let versions = HashMap::from([
+            ("minecraft".to_string(), "1.20.1".to_string()),
+            ("fabric".to_string(), "0.14.21".to_string()),
+        ]);
+
+        let context = server_context_from_versions(&versions).unwrap();
+
+        assert_eq!(context.minecraft_version, "1.20.1");
+        assert_eq!(context.loader_type, LoaderType::Fabric);
+        assert_eq!(context.loader_version, "0.14.21");
+    }
+
+    #[test]
+    fn builds_mod_info_from_metafile() {
+        let meta = metafile(
+            r#"
+name = "Lithium"
+filename = "lithium-fabric-0.11.2.jar"
+side = "both"
+
+[download]
+url = "https://cdn.modrinth.com/data/gvQqBUqZ/versions/abc/lithium-fabric-0.11.2.jar"
+hash-format = "sha1"
+hash = "deadbeef"
+"#,
+        );
+
+        let mod_info = build_mod_info(Path::new("mods/lithium.pw.toml"), meta, false)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(mod_info.hash_algo, "sha1");
+        assert_eq!(mod_info.side, SideType::Both);
+        assert_eq!(
+            mod_info.install_path,
+            Some(PathBuf::from("mods/lithium-fabric-0.11.2.jar"))
+        );
+    }
+
+    #[test]
+    fn resolves_curseforge_metadata_downloads() {
+        let meta = metafile(
+            r#"
+name = "JEI"
+filename = "jei-1.20.1.jar"
+side = "both"
+
+[download]
+hash-format = "murmur2"
+hash = "12345"
+mode = "metadata:curseforge"
+
+[update.curseforge]
+file-id = 4712868
+project-id = 238222
+"#,
+        );
+
+        let mod_info = build_mod_info(Path::new("mods/jei.pw.toml"), meta, false)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            mod_info.download_urls,
+            vec!["https://www.curseforge.com/api/v1/mods/238222/files/4712868/download"]
+        );
+        assert_eq!(mod_info.hash_algo, "none");
+    }
+
+    #[test]
+    fn skips_client_side_metafiles_unless_kept() {
+        let content = r#"
+name = "Sodium"
+filename = "sodium.jar"
+side = "client"
+
+[download]
+url = "https://cdn.modrinth.com/data/AANobbMI/versions/abc/sodium.jar"
+hash-format = "sha1"
+hash = "deadbeef"
+"#;
+
+        assert!(
+            build_mod_info(Path::new("mods/sodium.pw.toml"), metafile(content), false)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            build_mod_info(Path::new("mods/sodium.pw.toml"), metafile(content), true)
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn verifies_index_hashes() {
+        let sha256_of_abc = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+        assert!(hash_matches(b"abc", "sha256", sha256_of_abc).unwrap());
+        assert!(!hash_matches(b"abd", "sha256", sha256_of_abc).unwrap());
+        assert!(hash_matches(b"abc", "md5", "900150983cd24fb0d6963f7d28e17f72").unwrap());
+        assert!(hash_matches(b" \r\n", "murmur2", "1540447798").unwrap());
+        assert_eq!(murmur2(b"a b\tc\r\n"), murmur2(b"abc"));
+        assert!(hash_matches(b"abc", "crc32", "352441c2").is_err());
+    }
+}
@@ -0,0 +1,341 @@
+use crate::models::{LoaderType, ModInfo, ModMetadata, ServerContext, SideType};
+use crate::ops::mod_cache;
+use crate::parsers::rules::{FilterRules, RuleAction, RuleTarget};
+use crate::parsers::source;
+use crate::utils::sanitize_filename;
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+const CLIENT_ONLY_ENTRIES: [&str; 12] = [
+    "mods/",
+    "saves/",
+    "screenshots/",
+    "logs/",
+    "crash-reports/",
+    "resourcepacks/",
+    "shaderpacks/",
+    "options.txt",
+    "optionsof.txt",
+    "optionsshaders.txt",
+    "servers.dat",
+    "usercache.json",
+];
+
+#[derive(Debug, Deserialize)]
+struct MmcPack {
+    components: Vec<MmcComponent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MmcComponent {
+    uid: String,
+    version: Option<String>,
+    #[serde(rename = "cachedVersion")]
+    cached_version: Option<String>,
+}
+
+pub fn game_dir_prefix(path: &Path) -> Result<String> {
+    let names = source::open(path)?.file_names();
+    let root = find_instance_root(names.iter().map(String::as_str))
+        .with_context(|| "mmc-pack.json not found in archive")?;
+    Ok(find_game_dir(names.iter().map(String::as_str), &root))
+}
+
+pub fn is_server_override(relative_path: &str) -> bool {
+    !CLIENT_ONLY_ENTRIES.iter().any(|entry| {
+        if entry.ends_with('/') {
+            relative_path.starts_with(entry)
+        } else {
+            relative_path == *entry
+        }
+    })
+}
+
+pub fn parse_prism(
+    path: &Path,
+    keep_client: bool,
+    rules: &FilterRules,
+) -> Result<(ServerContext, Vec<ModInfo>)> {
+    parse_prism_in(path, &mod_cache::cache_root()?, keep_client, rules)
+}
+
+/// Bundled jars are stored in the mod cache under `cache_root`, which downloads restore them from.
+fn parse_prism_in(
+    path: &Path,
+    cache_root: &Path,
+    keep_client: bool,
+    rules: &FilterRules,
+) -> Result<(ServerContext, Vec<ModInfo>)> {
+    let mut source = source::open(path)?;
+    let names = source.file_names();
+
+    let root = find_instance_root(names.iter().map(String::as_str))
+        .with_context(|| "mmc-pack.json not found in archive")?;
+    let mods_prefix = format!(
+        "{}mods/",
+        find_game_dir(names.iter().map(String::as_str), &root)
+    );
+
+    let pack_content = source.read_to_string(&format!("{}mmc-pack.json", root))?;
+    let pack: MmcPack =
+        serde_json::from_str(&pack_content).with_context(|| "Failed to parse mmc-pack.json")?;
+    let server_context = server_context_from_components(&pack.components)?;
+
+    let mut mods = Vec::new();
+    for name in names
+        .iter()
+        .filter(|name| is_bundled_jar(name, &mods_prefix))
+    {
+        let mut bytes = Vec::new();
+        source.open_file(name)?.read_to_end(&mut bytes)?;
+
+        let file_name = sanitize_filename(&name[mods_prefix.len()..]);
+        let metadata = ModMetadata::extract_from_reader(Cursor::new(&bytes)).unwrap_or_default();
+
+        let hash = hex::encode(Sha1::digest(&bytes));
+        // Bundled jars carry no side of their own, so the declared side in their metadata decides.
+        let side = metadata.side.clone().unwrap_or(SideType::Both);
+        let target = RuleTarget {
+            mod_id: metadata.mod_id.as_deref(),
+            file_name: Some(&file_name),
+            hashes: vec![&hash],
+            ..RuleTarget::default()
+        };
+        match rules.action(&target) {
+            Some(RuleAction::Remove) => continue,
+            Some(RuleAction::Keep) => {}
+            None if side == SideType::Client && !keep_client => continue,
+            None => {}
+        }
+
+        let mut staged = tempfile::NamedTempFile::new()?;
+        staged
+            .write_all(&bytes)
+            .with_context(|| format!("Failed to stage bundled jar: {}", name))?;
+        let sha512 = mod_cache::store_into(cache_root, staged.path())
+            .with_context(|| format!("Failed to cache bundled jar: {}", name))?;
+        let cached_path = mod_cache::blob_path(cache_root, "sha512", &sha512)
+            .with_context(|| format!("Bundled jar missing from the mod cache: {}", name))?;
+        let cached_url = Url::from_file_path(&cached_path)
+            .map_err(|_| anyhow::anyhow!("Invalid cache path: {:?}", cached_path))?;
+
+        mods.push(ModInfo {
+            name: metadata.get_display_name(&file_name),
+            file_name: file_name.clone(),
+            download_urls: vec![cached_url.to_string()],
+            hash: sha512,
+            hash_algo: "sha512".to_string(),
+            side,
+            is_required: true,
+            install_path: Some(PathBuf::from("mods").join(&file_name)),
+        });
+    }
+
+    Ok((server_context, mods))
+}
+
+pub(crate) fn find_instance_root<'a>(names: impl Iterator<Item = &'a str>) -> Option<String> {
+    names
+        .filter_map(|name| name.strip_suffix("mmc-pack.json"))
+        .filter(|root| root.is_empty() || root.ends_with('/'))
+        .min_by_key(|root| root.len())
+        .map(str::to_string)
+}
+
+fn find_game_dir<'a>(mut names: impl Iterator<Item = &'a str>, root: &str) -> String {
+    let dot_minecraft = format!("{}.minecraft/", root);
+    if names.any(|name| name.starts_with(&dot_minecraft)) {
+        dot_minecraft
+    } else {
+        format!("{}minecraft/", root)
+    }
+}
+
+fn is_bundled_jar(name: &str, mods_prefix: &str) -> bool {
+    name.strip_prefix(mods_prefix)
+        .is_some_and(|rest| !rest.contains('/') && rest.ends_with(".jar"))
+}
+
+fn server_context_from_components(components: &[MmcComponent]) -> Result<ServerContext> {
+    let version_of = |uid: &str| {
+        components
+            .iter()
+            .find(|component| component.uid == uid)
+            .and_then(|component| {
+                component
+                    .version
+                    .clone()
+                    .or_else(|| component.cached_version.clone())
+            })
+    };
+
+    let minecraft_version =
+        version_of("net.minecraft").with_context(|| "net.minecraft not found in mmc-pack.json")?;
+
+    let (loader_type, loader_version) = if let Some(v) = version_of("net.fabricmc.fabric-loader") {
+        (LoaderType::Fabric, v)
+    } else if let Some(v) = version_of("net.minecraftforge") {
+        (LoaderType::Forge, v)
+    } else if let Some(v) = version_of("net.neoforged") {
+        (LoaderType::NeoForge, v)
+    } else if let Some(v) = version_of("org.quiltmc.quilt-loader") {
+        (LoaderType::Quilt, v)
+    } else {
+        bail!("Unsupported or missing loader component in mmc-pack.json");
+    };
+
+    Ok(ServerContext {
+        minecraft_version,
+        loader_type,
+        loader_version,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        MmcPack, find_game_dir, find_instance_root, is_bundled_jar, is_server_override,
+        parse_prism_in, server_context_from_components,
+    };
+    use crate::models::{LoaderType, SideType};
+    use crate::parsers::rules::FilterRules;
+    use crate::test_support::{temp_dir, write_zip};
+    use std::io::Write;
+    use std::path::Path;
+
+    fn fabric_jar(dir: &Path, mod_id: &str, environment: &str) -> Vec<u8> {
+        let path = dir.join(format!("{}.jar", mod_id));
+        let metadata = format!(
+            r#"{{"schemaVersion": 1, "id": "{}", "version": "1.0.0", "environment": "{}"}}"#,
+            mod_id, environment
+        );
+        write_zip(&path, &[("fabric.mod.json", &metadata)]);
+        std::fs::read(path).unwrap()
+    }
+
+    fn write_instance(path: &Path, jars: &[(&str, Vec<u8>)]) {
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        let pack = r#"{"components": [
+            {"uid": "net.minecraft", "version": "1.20.1"},
+            {"uid": "net.fabricmc.fabric-loader", "version": "0.15.7"}
+        ]}"#;
+        for (name, contents) in [
+            ("Pack/instance.cfg", b"name=Pack\n".as_slice()),
+            ("Pack/mmc-pack.json", pack.as_bytes()),
+        ] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        for (name, contents) in jars {
+            writer
+                .start_file(format!("Pack/.minecraft/mods/{}", name), options)
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn parses_an_instance_export_and_drops_client_only_jars() {
+        let root = temp_dir();
+        let instance = root.path().join("Pack.zip");
+        write_instance(
+            &instance,
+            &[
+                ("lithium.jar", fabric_jar(root.path(), "lithium", "*")),
+                ("sodium.jar", fabric_jar(root.path(), "sodium", "client")),
+            ],
+        );
+        let cache = root.path().join("cache");
+        let rules = FilterRules::default();
+
+        let (context, mods) = parse_prism_in(&instance, &cache, false, &rules).unwrap();
+
+        assert_eq!(context.minecraft_version, "1.20.1");
+        assert_eq!(context.loader_type, LoaderType::Fabric);
+        assert_eq!(context.loader_version, "0.15.7");
+        assert_eq!(mods.len(), 1);
+        assert_eq!(mods[0].file_name, "lithium.jar");
+        assert_eq!(mods[0].side, SideType::Both);
+        assert_eq!(mods[0].hash_algo, "sha512");
+        let cached = url::Url::parse(&mods[0].download_urls[0])
+            .unwrap()
+            .to_file_path()
+            .unwrap();
+        assert!(cached.starts_with(&cache));
+
+        let (_, mods) = parse_prism_in(&instance, &cache, true, &rules).unwrap();
+        let sides: Vec<_> = mods
+            .iter()
+            .map(|m| (m.file_name.as_str(), &m.side))
+            .collect();
+        assert!(sides.contains(&("sodium.jar", &SideType::Client)));
+        assert_eq!(mods.len(), 2);
+    }
+
+    #[test]
+    fn reads_loader_from_mmc_components() {
+        let pack: MmcPack = serde_json::from_str(
+            r#"{
+                "components": [
+                    {"uid": "org.lwjgl3", "version": "3.3.1"},
+                    {"uid": "net.minecraft", "version": "1.20.1"},
+                    {"uid": "net.neoforged", "cachedVersion": "47.1.106"}
+                ],
+                "formatVersion": 1
+            }"#,
+        )
+        .unwrap();
+
+        let context = server_context_from_components(&pack.components).unwrap();
+
+        assert_eq!(context.minecraft_version, "1.20.1");
+        assert_eq!(context.loader_type, LoaderType::NeoForge);
+        assert_eq!(context.loader_version, "47.1.106");
+    }
+
+    #[test]
+    fn locates_instance_root_and_game_dir() {
+        let names = [
+            "My Instance/instance.cfg",
+            "My Instance/mmc-pack.json",
+            "My Instance/.minecraft/mods/sodium.jar",
+        ];
+
+        let root = find_instance_root(names.into_iter()).unwrap();
+
+        assert_eq!(root, "My Instance/");
+        assert_eq!(
+            find_game_dir(names.into_iter(), &root),
+            "My Instance/.minecraft/"
+        );
+    }
+
+    #[test]
+    fn only_picks_top_level_jars_from_mods() {
+        let prefix = ".minecraft/mods/";
+
+        assert!(is_bundled_jar(".minecraft/mods/lithium.jar", prefix));
+        assert!(!is_bundled_jar(
+            ".minecraft/mods/lithium.jar.disabled",
+            prefix
+        ));
+        assert!(!is_bundled_jar(
+            ".minecraft/mods/.index/lithium.jar",
+            prefix
+        ));
+    }
+
+    #[test]
+    fn skips_client_state_when_copying_overrides() {
+        assert!(is_server_override("config/lithium.properties"));
+        assert!(!is_server_override("options.txt"));
+        assert!(!is_server_override("saves/World/level.dat"));
+        assert!(!is_server_override("mods/lithium.jar"));
+    }
+}
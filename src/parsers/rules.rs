@@ -0,0 +1,296 @@
+use crate::models::ModInfo;
+use crate::utils::cache_dir;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleAction {
+    Keep,
+    Remove,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RulesFile {
+    force_keep: RuleSet,
+    force_remove: RuleSet,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RuleSet {
+    mod_ids: Vec<String>,
+    project_ids: Vec<String>,
+    files: Vec<String>,
+    hashes: Vec<String>,
+}
+
+/// What is known about a mod at the point a rule is checked.
+#[derive(Debug, Default)]
+pub struct RuleTarget<'a> {
+    pub mod_id: Option<&'a str>,
+    pub project_id: Option<&'a str>,
+    pub file_name: Option<&'a str>,
+    pub hashes: Vec<&'a str>,
+}
+
+/// `force_keep`/`force_remove` rules, ordered from the per-pack file to the global one.
+#[derive(Debug, Default)]
+pub struct FilterRules {
+    files: Vec<RulesFile>,
+}
+
+pub fn global_rules_path() -> Result<PathBuf> {
+    Ok(cache_dir()?.join("filter_rules.toml"))
+}
+
+impl FilterRules {
+    pub fn load(pack_rules: Option<&Path>) -> Result<Self> {
+        let mut files = Vec::new();
+        if let Some(path) = pack_rules {
+            files.push(read_rules_file(path)?);
+        }
+
+        let global = global_rules_path()?;
+        if global.exists() {
+            files.push(read_rules_file(&global)?);
+        }
+
+        Ok(Self { files })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn from_toml(contents: &[&str]) -> Self {
+        Self {
+            files: contents
+                .iter()
+                .map(|content| toml::from_str(content).unwrap())
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files
+            .iter()
+            .all(|file| file.force_keep.is_empty() && file.force_remove.is_empty())
+    }
+
+    pub fn has_hash_rules(&self) -> bool {
+        self.files
+            .iter()
+            .any(|file| !file.force_keep.hashes.is_empty() || !file.force_remove.hashes.is_empty())
+    }
+
+    /// Returns the action of the first rules file that matches; within a file `force_keep` wins.
+    pub fn action(&self, target: &RuleTarget) -> Option<RuleAction> {
+        self.files.iter().find_map(|file| {
+            if file.force_keep.matches(target) {
+                Some(RuleAction::Keep)
+            } else if file.force_remove.matches(target) {
+                Some(RuleAction::Remove)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Pack file names of mods that `force_keep` pins before they are downloaded.
+    pub fn kept_file_names(&self, mods: &[ModInfo]) -> HashSet<String> {
+        mods.iter()
+            .filter(|mod_info| {
+                let project_id = mod_info
+                    .download_urls
+                    .iter()
+                    .find_map(|url| project_id_from_url(url));
+                let target = RuleTarget {
+                    project_id: project_id.as_deref(),
+                    file_name: Some(&mod_info.file_name),
+                    hashes: vec![mod_info.hash.as_str()],
+                    ..RuleTarget::default()
+                };
+                self.action(&target) == Some(RuleAction::Keep)
+            })
+            .map(|mod_info| mod_info.file_name.clone())
+            .collect()
+    }
+}
+
+impl RuleSet {
+    fn is_empty(&self) -> bool {
+        self.mod_ids.is_empty()
+            && self.project_ids.is_empty()
+            && self.files.is_empty()
+            && self.hashes.is_empty()
+    }
+
+    fn matches(&self, target: &RuleTarget) -> bool {
+        target
+            .mod_id
+            .is_some_and(|mod_id| self.mod_ids.iter().any(|id| id == mod_id))
+            || target
+                .project_id
+                .is_some_and(|project_id| self.project_ids.iter().any(|id| id == project_id))
+            || target.file_name.is_some_and(|file_name| {
+                self.files
+                    .iter()
+                    .any(|pattern| glob_matches(pattern, file_name))
+            })
+            || target.hashes.iter().any(|hash| {
+                !hash.is_empty()
+                    && self
+                        .hashes
+                        .iter()
+                        .any(|rule| rule.eq_ignore_ascii_case(hash))
+            })
+    }
+}
+
+fn read_rules_file(path: &Path) -> Result<RulesFile> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read rules file: {:?}", path))?;
+    toml::from_str(&content).with_context(|| format!("Failed to parse rules file: {:?}", path))
+}
+
+/// Extracts the project ID from Modrinth CDN and CurseForge download URLs.
+pub fn project_id_from_url(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let segments: Vec<&str> = url.path_segments()?.collect();
+
+    let after = |marker: &str| {
+        segments
+            .iter()
+            .position(|segment| *segment == marker)
+            .and_then(|index| segments.get(index + 1))
+            .map(|id| id.to_string())
+    };
+
+    match url.host_str()? {
+        "cdn.modrinth.com" => after("data"),
+        host if host.ends_with("curseforge.com") => after("mods"),
+        _ => None,
+    }
+}
+
+/// Case-insensitive match supporting `*` and `?` wildcards.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FilterRules, RuleAction, RuleTarget, glob_matches, project_id_from_url};
+
+    #[test]
+    fn matches_file_name_globs() {
+        assert!(glob_matches("iris-*.jar", "Iris-1.6.4+1.20.1.jar"));
+        assert!(glob_matches("*client*", "modernfix-client-5.0.jar"));
+        assert!(glob_matches("sodium-?.jar", "sodium-5.jar"));
+        assert!(!glob_matches("iris-*.jar", "irisshaders.jar"));
+    }
+
+    #[test]
+    fn applies_rules_by_id_file_and_hash() {
+        let rules = FilterRules::from_toml(&[r#"
+            [force_keep]
+            mod_ids = ["sodium"]
+            [force_remove]
+            project_ids = ["394468"]
+            files = ["*-client-*.jar"]
+            hashes = ["ABC123"]
+        "#]);
+
+        let action = |target: RuleTarget| rules.action(&target);
+        assert_eq!(
+            action(RuleTarget {
+                mod_id: Some("sodium"),
+                file_name: Some("sodium-client-0.5.jar"),
+                ..RuleTarget::default()
+            }),
+            Some(RuleAction::Keep)
+        );
+        assert_eq!(
+            action(RuleTarget {
+                project_id: Some("394468"),
+                ..RuleTarget::default()
+            }),
+            Some(RuleAction::Remove)
+        );
+        assert_eq!(
+            action(RuleTarget {
+                hashes: vec!["abc123"],
+                ..RuleTarget::default()
+            }),
+            Some(RuleAction::Remove)
+        );
+        assert_eq!(
+            action(RuleTarget {
+                mod_id: Some("lithium"),
+                hashes: vec![""],
+                ..RuleTarget::default()
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn pack_rules_take_precedence_over_global_rules() {
+        let rules = FilterRules::from_toml(&[
+            "[force_remove]\nmod_ids = [\"iris\"]",
+            "[force_keep]\nmod_ids = [\"iris\"]",
+        ]);
+
+        assert_eq!(
+            rules.action(&RuleTarget {
+                mod_id: Some("iris"),
+                ..RuleTarget::default()
+            }),
+            Some(RuleAction::Remove)
+        );
+    }
+
+    #[test]
+    fn extracts_project_ids_from_download_urls() {
+        assert_eq!(
+            project_id_from_url("https://cdn.modrinth.com/data/AANobbMI/versions/abc/sodium.jar")
+                .as_deref(),
+            Some("AANobbMI")
+        );
+        assert_eq!(
+            project_id_from_url(
+                "https://www.curseforge.com/api/v1/mods/394468/files/4567/download"
+            )
+            .as_deref(),
+            Some("394468")
+        );
+        assert_eq!(project_id_from_url("https://github.com/a/b.jar"), None);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::utils::is_safe_relative_path;
+use anyhow::{Context, Result, bail};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+pub trait PackSource {
+    fn file_names(&self) -> Vec<String>;
+
+    fn open_file(&mut self, name: &str) -> Result<Box<dyn Read + '_>>;
+
+    fn read_to_string(&mut self, name: &str) -> Result<String> {
+        let mut content = String::new();
+        self.open_file(name)
+            .with_context(|| format!("{} not found in pack", name))?
+            .read_to_string(&mut content)?;
+        Ok(content)
+    }
+}
+
+pub fn open(path: &Path) -> Result<Box<dyn PackSource>> {
+    if path.is_dir() {
+        Ok(Box::new(DirSource {
+            root: path.to_path_buf(),
+        }))
+    } else {
+        let file = File::open(path).with_context(|| format!("Failed to open file: {:?}", path))?;
+        let archive = ZipArchive::new(file).with_context(|| "Failed to open zip archive")?;
+        Ok(Box::new(ZipSource { archive }))
+    }
+}
+
+struct ZipSource {
+    archive: ZipArchive<File>,
+}
+
+impl PackSource for ZipSource {
+    fn file_names(&self) -> Vec<String> {
+        self.archive
+            .file_names()
+            .filter(|name| !name.ends_with('/'))
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn open_file(&mut self, name: &str) -> Result<Box<dyn Read + '_>> {
+        Ok(Box::new(self.archive.by_name(name)?))
+    }
+}
+
+struct DirSource {
+    root: PathBuf,
+}
+
+impl PackSource for DirSource {
+    fn file_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        collect_file_names(&self.root, "", &mut names);
+        names
+    }
+
+    fn open_file(&mut self, name: &str) -> Result<Box<dyn Read + '_>> {
+        if !is_safe_relative_path(Path::new(name)) {
+            bail!("Malicious path detected in modpack: {}", name);
+        }
+        let path = self.root.join(name);
+        let file = File::open(&path).with_context(|| format!("Failed to open {:?}", path))?;
+        Ok(Box::new(file))
+    }
+}
+
+fn collect_file_names(dir: &Path, prefix: &str, names: &mut Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let name = format!("{}{}", prefix, file_name);
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_dir() {
+            collect_file_names(&entry.path(), &format!("{}/", name), names);
+        } else if file_type.is_file() {
+            names.push(name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::open;
+    use crate::test_support::temp_dir;
+
+    #[test]
+    fn lists_directory_files_with_forward_slashes() {
+        let root = temp_dir();
+        let root = root.path();
+        std::fs::create_dir_all(root.join("overrides/config")).unwrap();
+        std::fs::write(root.join("modrinth.index.json"), "{}").unwrap();
+        std::fs::write(root.join("overrides/config/a.toml"), "a = 1").unwrap();
+
+        let mut source = open(root).unwrap();
+        let mut names = source.file_names();
+        names.sort();
+
+        assert_eq!(
+            names,
+            vec!["modrinth.index.json", "overrides/config/a.toml"]
+        );
+        assert_eq!(
+            source.read_to_string("overrides/config/a.toml").unwrap(),
+            "a = 1"
+        );
+        assert!(source.open_file("../escape").is_err());
+    }
+}
@@ -0,0 +1,398 @@
+use crate::http::with_proxy;
+use crate::resolvers::download_to_cache;
+use crate::retry::RetryPolicy;
+use crate::utils::{BROWSER_USER_AGENT, cache_dir, sanitize_filename};
+use anyhow::{Context, Result, bail};
+use reqwest::{
+    Client,
+    header::{HeaderMap, HeaderValue},
+};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_API_BASE: &str = "https://www.curseforge.com/api/v1";
+
+#[derive(Debug, Deserialize)]
+struct CfFileResponse {
+    data: CfFile,
+}
+
+#[derive(Debug, Deserialize)]
+struct CfFileListResponse {
+    data: Vec<CfFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CfFile {
+    id: u32,
+    #[serde(rename = "fileName")]
+    file_name: String,
+    #[serde(rename = "serverPackFileId", default)]
+    server_pack_file_id: Option<u32>,
+    #[serde(rename = "isServerPack", default)]
+    is_server_pack: bool,
+    #[serde(rename = "gameVersions", default)]
+    game_versions: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectSpec {
+    pub project_id: u32,
+    pub file_id: Option<u32>,
+}
+
+#[derive(Debug)]
+pub struct ResolvedPack {
+    pub client_pack: PathBuf,
+    pub server_pack_file_id: Option<u32>,
+}
+
+pub fn parse_spec(spec: &str) -> Result<ProjectSpec, String> {
+    let (project, file) = match spec.split_once('/') {
+        Some((project, file)) => (project, Some(file)),
+        None => (spec, None),
+    };
+
+    let project_id = project
+        .parse::<u32>()
+        .map_err(|_| format!("Invalid CurseForge project ID: '{}'", project))?;
+    let file_id = file
+        .map(|file| {
+            file.parse::<u32>()
+                .map_err(|_| format!("Invalid CurseForge file ID: '{}'", file))
+        })
+        .transpose()?;
+
+    Ok(ProjectSpec {
+        project_id,
+        file_id,
+    })
+}
+
+pub async fn resolve_modpack(
+    api_base: &str,
+    spec: &ProjectSpec,
+    game_version: Option<&str>,
+    loader: Option<&str>,
+    proxy: Option<&str>,
+    retry: RetryPolicy,
+) -> Result<ResolvedPack> {
+    resolve_modpack_in(
+        &cache_dir()?,
+        api_base,
+        spec,
+        game_version,
+        loader,
+        proxy,
+        retry,
+    )
+    .await
+}
+
+/// Downloads the selected pack under `cache_root` instead of the user cache directory.
+async fn resolve_modpack_in(
+    cache_root: &Path,
+    api_base: &str,
+    spec: &ProjectSpec,
+    game_version: Option<&str>,
+    loader: Option<&str>,
+    proxy: Option<&str>,
+    retry: RetryPolicy,
+) -> Result<ResolvedPack> {
+    let client = build_client(proxy)?;
+    let api_base = api_base.trim_end_matches('/');
+
+    let file = match spec.file_id {
+        Some(file_id) => fetch_file(&client, retry, api_base, spec.project_id, file_id).await?,
+        None => {
+            let files = fetch_files(&client, retry, api_base, spec.project_id).await?;
+            select_file(files, game_version, loader).with_context(|| {
+                format!(
+                    "No CurseForge file of project {} matches the requested filters",
+                    spec.project_id
+                )
+            })?
+        }
+    };
+
+    let client_pack =
+        download_pack_file(cache_root, &client, retry, api_base, spec.project_id, &file).await?;
+
+    Ok(ResolvedPack {
+        client_pack,
+        server_pack_file_id: file.server_pack_file_id,
+    })
+}
+
+pub async fn download_server_pack(
+    api_base: &str,
+    project_id: u32,
+    file_id: u32,
+    proxy: Option<&str>,
+    retry: RetryPolicy,
+) -> Result<PathBuf> {
+    download_server_pack_in(&cache_dir()?, api_base, project_id, file_id, proxy, retry).await
+}
+
+async fn download_server_pack_in(
+    cache_root: &Path,
+    api_base: &str,
+    project_id: u32,
+    file_id: u32,
+    proxy: Option<&str>,
+    retry: RetryPolicy,
+) -> Result<PathBuf> {
+    let client = build_client(proxy)?;
+    let api_base = api_base.trim_end_matches('/');
+
+    let file = fetch_file(&client, retry, api_base, project_id, file_id).await?;
+    download_pack_file(cache_root, &client, retry, api_base, project_id, &file).await
+}
+
+fn build_client(proxy: Option<&str>) -> Result<Client> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Referer",
+        HeaderValue::from_static("https://www.curseforge.com/"),
+    );
+    headers.insert(
+        "Origin",
+        HeaderValue::from_static("https://www.curseforge.com"),
+    );
+
+    let client_builder = Client::builder()
+        .redirect(reqwest::redirect::Policy::limited(10))
+        .user_agent(BROWSER_USER_AGENT)
+        .default_headers(headers);
+
+    with_proxy(client_builder, proxy)?
+        .build()
+        .map_err(Into::into)
+}
+
+async fn fetch_file(
+    client: &Client,
+    retry: RetryPolicy,
+    api_base: &str,
+    project_id: u32,
+    file_id: u32,
+) -> Result<CfFile> {
+    let url = format!("{}/mods/{}/files/{}", api_base, project_id, file_id);
+    let response = retry
+        .send(client.get(&url).header("Accept", "application/json"))
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to resolve CurseForge metadata for project {} file {}",
+                project_id, file_id
+            )
+        })?;
+
+    if !response.status().is_success() {
+        bail!(
+            "Failed to resolve CurseForge metadata for project {} file {}: {}",
+            project_id,
+            file_id,
+            response.status()
+        );
+    }
+
+    let json = response.json::<CfFileResponse>().await.with_context(|| {
+        format!(
+            "Failed to decode CurseForge metadata for project {} file {}",
+            project_id, file_id
+        )
+    })?;
+
+    Ok(json.data)
+}
+
+async fn fetch_files(
+    client: &Client,
+    retry: RetryPolicy,
+    api_base: &str,
+    project_id: u32,
+) -> Result<Vec<CfFile>> {
+    let url = format!(
+        "{}/mods/{}/files?pageIndex=0&pageSize=50&sort=dateCreated&sortDescending=true",
+        api_base, project_id
+    );
+    let response = retry
+        .send(client.get(&url).header("Accept", "application/json"))
+        .await
+        .with_context(|| format!("Failed to list CurseForge files for project {}", project_id))?;
+
+    if !response.status().is_success() {
+        bail!(
+            "Failed to list CurseForge files for project {}: {}",
+            project_id,
+            response.status()
+        );
+    }
+
+    let json = response
+        .json::<CfFileListResponse>()
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to decode CurseForge files for project {}",
+                project_id
+            )
+        })?;
+
+    Ok(json.data)
+}
+
+fn select_file(
+    files: Vec<CfFile>,
+    game_version: Option<&str>,
+    loader: Option<&str>,
+) -> Option<CfFile> {
+    let declares = |file: &CfFile, wanted: &str| {
+        file.game_versions
+            .iter()
+            .any(|version| version.eq_ignore_ascii_case(wanted))
+    };
+
+    files.into_iter().find(|file| {
+        !file.is_server_pack
+            && game_version.is_none_or(|wanted| declares(file, wanted))
+            && loader.is_none_or(|wanted| declares(file, wanted))
+    })
+}
+
+async fn download_pack_file(
+    cache_root: &Path,
+    client: &Client,
+    retry: RetryPolicy,
+    api_base: &str,
+    project_id: u32,
+    file: &CfFile,
+) -> Result<PathBuf> {
+    let pack_path = cache_root
+        .join("curseforge")
+        .join(project_id.to_string())
+        .join(sanitize_filename(&file.file_name));
+    let url = format!(
+        "{}/mods/{}/files/{}/download",
+        api_base, project_id, file.id
+    );
+
+    download_to_cache(client, retry, &url, &pack_path, &file.file_name, None).await?;
+
+    Ok(pack_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CfFileListResponse, ProjectSpec, download_server_pack_in, parse_spec, resolve_modpack_in,
+        select_file,
+    };
+    use crate::resolvers::test_server::TestServer;
+    use crate::retry::RetryPolicy;
+    use crate::test_support::temp_dir;
+    use std::time::Duration;
+
+    fn files() -> CfFileListResponse {
+        serde_json::from_str(
+            r#"{
+                "data": [
+                    {"id": 3, "fileName": "Pack-Server-2.0.zip", "isServerPack": true, "gameVersions": ["1.20.1", "Forge"]},
+                    {"id": 2, "fileName": "Pack-2.0.zip", "serverPackFileId": 3, "gameVersions": ["1.20.1", "Forge"]},
+                    {"id": 1, "fileName": "Pack-1.0.zip", "gameVersions": ["1.19.2", "Forge"]}
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_project_and_optional_file_ids() {
+        assert_eq!(
+            parse_spec("123456/7890"),
+            Ok(ProjectSpec {
+                project_id: 123456,
+                file_id: Some(7890),
+            })
+        );
+        assert_eq!(parse_spec("123456").unwrap().file_id, None);
+        assert!(parse_spec("better-mc").is_err());
+        assert!(parse_spec("123456/latest").is_err());
+    }
+
+    #[test]
+    fn picks_newest_client_file_matching_filters() {
+        let newest = select_file(files().data, None, None).unwrap();
+        assert_eq!(newest.id, 2);
+        assert_eq!(newest.server_pack_file_id, Some(3));
+
+        let older = select_file(files().data, Some("1.19.2"), Some("forge")).unwrap();
+        assert_eq!(older.id, 1);
+        assert_eq!(older.server_pack_file_id, None);
+
+        assert!(select_file(files().data, None, Some("fabric")).is_none());
+    }
+
+    #[tokio::test]
+    async fn resolves_client_and_server_packs_through_the_api_base() {
+        let cache_root = temp_dir();
+        let project_id = 123456;
+        let server = TestServer::start(|_| {
+            let files = serde_json::json!({
+                "data": [
+                    {"id": 3, "fileName": "Pack-Server-2.0.zip", "isServerPack": true, "gameVersions": ["1.20.1", "Forge"]},
+                    {"id": 2, "fileName": "Pack-2.0.zip", "serverPackFileId": 3, "gameVersions": ["1.20.1", "Forge"]},
+                    {"id": 1, "fileName": "Pack-1.0.zip", "gameVersions": ["1.19.2", "Forge"]}
+                ]
+            });
+            let server_file = serde_json::json!({
+                "data": {"id": 3, "fileName": "Pack-Server-2.0.zip", "isServerPack": true}
+            });
+            let route = |suffix: &str| format!("/api/v1/mods/{}/files{}", project_id, suffix);
+            vec![
+                (route(""), files.to_string().into_bytes()),
+                (route("/2/download"), b"client pack".to_vec()),
+                (route("/3"), server_file.to_string().into_bytes()),
+                (route("/3/download"), b"server pack".to_vec()),
+            ]
+        });
+        let api_base = format!("{}/api/v1/", server.base);
+        let retry = RetryPolicy::new(0, Duration::ZERO);
+        let spec = ProjectSpec {
+            project_id,
+            file_id: None,
+        };
+
+        let resolved = resolve_modpack_in(
+            cache_root.path(),
+            &api_base,
+            &spec,
+            Some("1.20.1"),
+            Some("forge"),
+            None,
+            retry,
+        )
+        .await
+        .unwrap();
+        let server_pack = download_server_pack_in(
+            cache_root.path(),
+            &api_base,
+            project_id,
+            resolved.server_pack_file_id.unwrap(),
+            None,
+            retry,
+        )
+        .await
+        .unwrap();
+        let client_bytes = std::fs::read(&resolved.client_pack).unwrap();
+        let server_bytes = std::fs::read(&server_pack).unwrap();
+
+        assert_eq!(client_bytes, b"client pack");
+        assert_eq!(resolved.client_pack.file_name().unwrap(), "Pack-2.0.zip");
+        assert_eq!(server_bytes, b"server pack");
+        assert_eq!(server_pack.file_name().unwrap(), "Pack-Server-2.0.zip");
+        assert!(server_pack.starts_with(cache_root.path()));
+        assert!(server.requests()[0].contains("sortDescending=true"));
+    }
+}
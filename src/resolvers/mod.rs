@@ -0,0 +1,62 @@
+pub mod curseforge;
+pub mod modrinth;
+pub mod url;
+
+#[cfg(test)]
+mod test_server;
+
+use crate::http::download_with_progress;
+use crate::retry::RetryPolicy;
+use anyhow::{Result, bail};
+use reqwest::Client;
+use sha2::{Digest, Sha512};
+use std::path::Path;
+use tokio::fs;
+
+pub async fn download_to_cache(
+    client: &Client,
+    retry: RetryPolicy,
+    url: &str,
+    output_path: &Path,
+    label: &str,
+    expected_sha512: Option<&str>,
+) -> Result<()> {
+    if let Some(expected) = expected_sha512
+        && output_path.exists()
+        && sha512_of(output_path).await?.eq_ignore_ascii_case(expected)
+    {
+        return Ok(());
+    }
+
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let temp_path = output_path.with_extension("part");
+    let mut hasher = Sha512::new();
+    download_with_progress(client, retry, url, &temp_path, label, |chunk| {
+        hasher.update(chunk)
+    })
+    .await?;
+
+    if let Some(expected) = expected_sha512 {
+        let computed = hex::encode(hasher.finalize());
+        if !computed.eq_ignore_ascii_case(expected) {
+            let _ = fs::remove_file(&temp_path).await;
+            bail!(
+                "Hash mismatch for {}: expected {}, got {}",
+                label,
+                expected,
+                computed
+            );
+        }
+    }
+
+    fs::rename(&temp_path, output_path).await?;
+    Ok(())
+}
+
+async fn sha512_of(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).await?;
+    Ok(hex::encode(Sha512::digest(&bytes)))
+}
@@ -0,0 +1,468 @@
+use crate::http::build_client;
+use crate::resolvers::download_to_cache;
+use crate::retry::RetryPolicy;
+use crate::utils::{cache_dir, sanitize_filename};
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_API_BASE: &str = "https://api.modrinth.com/v2";
+
+#[derive(Debug, Deserialize)]
+struct ProjectVersion {
+    id: String,
+    version_number: String,
+    #[serde(default)]
+    game_versions: Vec<String>,
+    #[serde(default)]
+    loaders: Vec<String>,
+    files: Vec<VersionFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VersionFile {
+    url: String,
+    filename: String,
+    #[serde(default)]
+    primary: bool,
+    hashes: VersionFileHashes,
+}
+
+#[derive(Debug, Deserialize)]
+struct VersionFileHashes {
+    sha512: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct HashVersion {
+    project_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Project {
+    id: String,
+    server_side: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectSpec {
+    pub slug: String,
+    pub version: Option<String>,
+}
+
+pub fn parse_spec(spec: &str) -> Result<ProjectSpec, String> {
+    let (slug, version) = match spec.split_once('@') {
+        Some((slug, version)) => (slug, Some(version)),
+        None => (spec, None),
+    };
+
+    if slug.is_empty() || slug.contains('/') {
+        return Err(format!("Invalid Modrinth project slug: '{}'", slug));
+    }
+    if version.is_some_and(str::is_empty) {
+        return Err(format!("Missing version after '@' in 'modrinth:{}'", spec));
+    }
+
+    Ok(ProjectSpec {
+        slug: slug.to_string(),
+        version: version.map(str::to_string),
+    })
+}
+
+pub async fn resolve_modpack(
+    api_base: &str,
+    spec: &ProjectSpec,
+    game_version: Option<&str>,
+    loader: Option<&str>,
+    proxy: Option<&str>,
+    retry: RetryPolicy,
+) -> Result<PathBuf> {
+    resolve_modpack_in(
+        &cache_dir()?,
+        api_base,
+        spec,
+        game_version,
+        loader,
+        proxy,
+        retry,
+    )
+    .await
+}
+
+/// Downloads the selected pack under `cache_root` instead of the user cache directory.
+async fn resolve_modpack_in(
+    cache_root: &Path,
+    api_base: &str,
+    spec: &ProjectSpec,
+    game_version: Option<&str>,
+    loader: Option<&str>,
+    proxy: Option<&str>,
+    retry: RetryPolicy,
+) -> Result<PathBuf> {
+    let client = build_client(proxy)?;
+
+    let mut url = url::Url::parse(&format!(
+        "{}/project/{}/version",
+        api_base.trim_end_matches('/'),
+        urlencoding::encode(&spec.slug)
+    ))
+    .with_context(|| format!("Invalid Modrinth API base URL: {}", api_base))?;
+    if let Some(game_version) = game_version {
+        url.query_pairs_mut()
+            .append_pair("game_versions", &format!("[\"{}\"]", game_version));
+    }
+    if let Some(loader) = loader {
+        url.query_pairs_mut()
+            .append_pair("loaders", &format!("[\"{}\"]", loader));
+    }
+
+    let response = retry
+        .send(client.get(url.as_str()))
+        .await
+        .with_context(|| format!("Failed to query Modrinth versions for {}", spec.slug))?;
+
+    if !response.status().is_success() {
+        bail!(
+            "Failed to query Modrinth versions for {}: {}",
+            spec.slug,
+            response.status()
+        );
+    }
+
+    let versions = response
+        .json::<Vec<ProjectVersion>>()
+        .await
+        .with_context(|| format!("Failed to decode Modrinth versions for {}", spec.slug))?;
+
+    let version = select_version(&versions, spec.version.as_deref(), game_version, loader)
+        .with_context(|| match &spec.version {
+            Some(version) => format!(
+                "No Modrinth version '{}' of {} matches the requested filters",
+                version, spec.slug
+            ),
+            None => format!(
+                "No Modrinth version of {} matches the requested filters",
+                spec.slug
+            ),
+        })?;
+    let file = select_pack_file(&version.files).with_context(|| {
+        format!(
+            "Modrinth version {} of {} has no .mrpack file",
+            version.version_number, spec.slug
+        )
+    })?;
+
+    let pack_path = cache_root
+        .join("modrinth")
+        .join(sanitize_filename(&spec.slug))
+        .join(sanitize_filename(&file.filename));
+
+    download_to_cache(
+        &client,
+        retry,
+        &file.url,
+        &pack_path,
+        &file.filename,
+        Some(&file.hashes.sha512),
+    )
+    .await?;
+
+    Ok(pack_path)
+}
+
+/// Looks up jars by SHA-1 and returns each known hash's project `server_side` value.
+pub async fn lookup_server_sides(
+    api_base: &str,
+    sha1_hashes: &[String],
+    proxy: Option<&str>,
+    retry: RetryPolicy,
+) -> Result<HashMap<String, String>> {
+    if sha1_hashes.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let client = build_client(proxy)?;
+    let api_base = api_base.trim_end_matches('/');
+
+    let response = retry
+        .send(
+            client
+                .post(format!("{}/version_files", api_base))
+                .json(&serde_json::json!({ "hashes": sha1_hashes, "algorithm": "sha1" })),
+        )
+        .await
+        .context("Failed to query Modrinth version files")?;
+    if !response.status().is_success() {
+        bail!(
+            "Failed to query Modrinth version files: {}",
+            response.status()
+        );
+    }
+    let versions = response
+        .json::<HashMap<String, HashVersion>>()
+        .await
+        .context("Failed to decode Modrinth version files")?;
+    if versions.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let project_ids: HashSet<&str> = versions
+        .values()
+        .map(|version| version.project_id.as_str())
+        .collect();
+    let mut url = url::Url::parse(&format!("{}/projects", api_base))
+        .with_context(|| format!("Invalid Modrinth API base URL: {}", api_base))?;
+    url.query_pairs_mut()
+        .append_pair("ids", &serde_json::to_string(&project_ids)?);
+    let response = retry
+        .send(client.get(url.as_str()))
+        .await
+        .context("Failed to query Modrinth projects")?;
+    if !response.status().is_success() {
+        bail!("Failed to query Modrinth projects: {}", response.status());
+    }
+    let projects = response
+        .json::<Vec<Project>>()
+        .await
+        .context("Failed to decode Modrinth projects")?;
+
+    Ok(server_sides_by_hash(versions, projects))
+}
+
+fn server_sides_by_hash(
+    versions: HashMap<String, HashVersion>,
+    projects: Vec<Project>,
+) -> HashMap<String, String> {
+    let sides: HashMap<String, String> = projects
+        .into_iter()
+        .map(|project| (project.id, project.server_side))
+        .collect();
+
+    versions
+        .into_iter()
+        .filter_map(|(hash, version)| {
+            sides
+                .get(&version.project_id)
+                .map(|side| (hash.to_lowercase(), side.clone()))
+        })
+        .collect()
+}
+
+fn select_version<'a>(
+    versions: &'a [ProjectVersion],
+    wanted: Option<&str>,
+    game_version: Option<&str>,
+    loader: Option<&str>,
+) -> Option<&'a ProjectVersion> {
+    versions.iter().find(|version| {
+        wanted.is_none_or(|wanted| version.version_number == wanted || version.id == wanted)
+            && game_version
+                .is_none_or(|game_version| version.game_versions.iter().any(|v| v == game_version))
+            && loader.is_none_or(|loader| {
+                version
+                    .loaders
+                    .iter()
+                    .any(|l| l.eq_ignore_ascii_case(loader))
+            })
+    })
+}
+
+fn select_pack_file(files: &[VersionFile]) -> Option<&VersionFile> {
+    let is_mrpack = |file: &&VersionFile| file.filename.ends_with(".mrpack");
+
+    files
+        .iter()
+        .filter(is_mrpack)
+        .find(|file| file.primary)
+        .or_else(|| files.iter().find(is_mrpack))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ProjectSpec, ProjectVersion, lookup_server_sides, parse_spec, resolve_modpack_in,
+        select_pack_file, select_version, server_sides_by_hash,
+    };
+    use crate::resolvers::test_server::TestServer;
+    use crate::retry::RetryPolicy;
+    use crate::test_support::temp_dir;
+    use sha2::{Digest, Sha512};
+    use std::time::Duration;
+
+    fn versions() -> Vec<ProjectVersion> {
+        serde_json::from_str(
+            r#"[
+                {
+                    "id": "bbbb",
+                    "version_number": "v27",
+                    "game_versions": ["1.21.1"],
+                    "loaders": ["neoforge"],
+                    "files": [
+                        {"url": "https://cdn.modrinth.com/b.mrpack", "filename": "b.mrpack", "primary": true, "hashes": {"sha512": "bb", "sha1": "b"}}
+                    ]
+                },
+                {
+                    "id": "aaaa",
+                    "version_number": "v26",
+                    "game_versions": ["1.20.1"],
+                    "loaders": ["forge"],
+                    "files": [
+                        {"url": "https://cdn.modrinth.com/a.txt", "filename": "changelog.txt", "primary": true, "hashes": {"sha512": "cc"}},
+                        {"url": "https://cdn.modrinth.com/a.mrpack", "filename": "a.mrpack", "primary": false, "hashes": {"sha512": "aa"}}
+                    ]
+                }
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_slug_and_optional_version() {
+        assert_eq!(
+            parse_spec("better-mc@v26"),
+            Ok(ProjectSpec {
+                slug: "better-mc".to_string(),
+                version: Some("v26".to_string()),
+            })
+        );
+        assert_eq!(parse_spec("better-mc").unwrap().version, None);
+        assert!(parse_spec("").is_err());
+        assert!(parse_spec("better-mc@").is_err());
+    }
+
+    #[test]
+    fn picks_newest_version_matching_filters() {
+        let versions = versions();
+
+        assert_eq!(
+            select_version(&versions, None, None, None).unwrap().id,
+            "bbbb"
+        );
+        assert_eq!(
+            select_version(&versions, None, Some("1.20.1"), Some("Forge"))
+                .unwrap()
+                .id,
+            "aaaa"
+        );
+        assert_eq!(
+            select_version(&versions, Some("v26"), None, None)
+                .unwrap()
+                .id,
+            "aaaa"
+        );
+        assert!(select_version(&versions, Some("v26"), None, Some("fabric")).is_none());
+    }
+
+    #[test]
+    fn prefers_primary_mrpack_and_falls_back_to_any_mrpack() {
+        let versions = versions();
+
+        assert_eq!(
+            select_pack_file(&versions[0].files).unwrap().filename,
+            "b.mrpack"
+        );
+        assert_eq!(
+            select_pack_file(&versions[1].files).unwrap().filename,
+            "a.mrpack"
+        );
+    }
+
+    #[test]
+    fn maps_hashes_to_project_server_sides() {
+        let versions = serde_json::from_str(
+            r#"{
+                "AB12": {"project_id": "AANobbMI", "id": "v1"},
+                "cd34": {"project_id": "gvQqBUqZ", "id": "v2"},
+                "ef56": {"project_id": "missing", "id": "v3"}
+            }"#,
+        )
+        .unwrap();
+        let projects = serde_json::from_str(
+            r#"[
+                {"id": "AANobbMI", "server_side": "unsupported", "client_side": "required"},
+                {"id": "gvQqBUqZ", "server_side": "required", "client_side": "optional"}
+            ]"#,
+        )
+        .unwrap();
+
+        let sides = server_sides_by_hash(versions, projects);
+
+        assert_eq!(sides.len(), 2);
+        assert_eq!(sides["ab12"], "unsupported");
+        assert_eq!(sides["cd34"], "required");
+    }
+
+    #[tokio::test]
+    async fn resolves_and_looks_up_sides_through_the_api_base() {
+        let cache_root = temp_dir();
+        let slug = "better-mc".to_string();
+        let pack = b"pack bytes".to_vec();
+        let sha512 = hex::encode(Sha512::digest(&pack));
+        let server = TestServer::start(|base| {
+            let versions = serde_json::json!([
+                {
+                    "id": "bbbb",
+                    "version_number": "v27",
+                    "game_versions": ["1.21.1"],
+                    "loaders": ["neoforge"],
+                    "files": [{"url": format!("{}/b.mrpack", base), "filename": "b.mrpack", "primary": true, "hashes": {"sha512": "bb"}}]
+                },
+                {
+                    "id": "aaaa",
+                    "version_number": "v26",
+                    "game_versions": ["1.20.1"],
+                    "loaders": ["forge"],
+                    "files": [{"url": format!("{}/a.mrpack", base), "filename": "a.mrpack", "primary": true, "hashes": {"sha512": sha512}}]
+                }
+            ]);
+            vec![
+                (
+                    format!("/v2/project/{}/version", slug),
+                    versions.to_string().into_bytes(),
+                ),
+                ("/a.mrpack".to_string(), pack.clone()),
+                (
+                    "/v2/version_files".to_string(),
+                    br#"{"ab12": {"project_id": "AANobbMI"}}"#.to_vec(),
+                ),
+                (
+                    "/v2/projects".to_string(),
+                    br#"[{"id": "AANobbMI", "server_side": "unsupported"}]"#.to_vec(),
+                ),
+            ]
+        });
+        let api_base = format!("{}/v2/", server.base);
+        let retry = RetryPolicy::new(0, Duration::ZERO);
+        let spec = ProjectSpec {
+            slug: slug.clone(),
+            version: None,
+        };
+
+        let pack_path = resolve_modpack_in(
+            cache_root.path(),
+            &api_base,
+            &spec,
+            Some("1.20.1"),
+            Some("forge"),
+            None,
+            retry,
+        )
+        .await
+        .unwrap();
+        let sides = lookup_server_sides(&api_base, &["AB12".to_string()], None, retry)
+            .await
+            .unwrap();
+        let cached = std::fs::read(&pack_path).unwrap();
+
+        assert_eq!(cached, pack);
+        assert_eq!(pack_path.file_name().unwrap(), "a.mrpack");
+        assert!(pack_path.starts_with(cache_root.path()));
+        assert_eq!(sides["ab12"], "unsupported");
+        let requests = server.requests();
+        assert!(
+            requests[0].starts_with(&format!("GET /v2/project/{}/version?game_versions=", slug))
+        );
+        assert!(requests.contains(&"POST /v2/version_files".to_string()));
+    }
+}
@@ -0,0 +1,71 @@
+use crate::http::build_client;
+use crate::parsers::{PackFormat, detect_format};
+use crate::resolvers::download_to_cache;
+use crate::retry::RetryPolicy;
+use crate::utils::{cache_dir, sanitize_filename};
+use anyhow::{Context, Result};
+use sha1::{Digest, Sha1};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+pub fn is_packwiz_url(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| url.path().ends_with(".toml"))
+}
+
+pub async fn download_pack(url: &str, proxy: Option<&str>, retry: RetryPolicy) -> Result<PathBuf> {
+    let parsed = Url::parse(url).with_context(|| format!("Invalid input URL: {}", url))?;
+    let stem = pack_stem(&parsed);
+    let download_dir = cache_dir()?
+        .join("downloads")
+        .join(&hex::encode(Sha1::digest(url.as_bytes()))[..12]);
+    let download_path = download_dir.join(format!("{}.download", stem));
+
+    let client = build_client(proxy)?;
+    download_to_cache(&client, retry, url, &download_path, &stem, None).await?;
+
+    let extension = match detect_format(&download_path)? {
+        PackFormat::Modrinth => "mrpack",
+        _ => "zip",
+    };
+    let pack_path = download_dir.join(format!("{}.{}", stem, extension));
+    std::fs::rename(&download_path, &pack_path)?;
+
+    Ok(pack_path)
+}
+
+fn pack_stem(url: &Url) -> String {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .and_then(|segment| urlencoding::decode(segment).ok())
+        .map(|name| {
+            Path::new(&*name)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        })
+        .filter(|stem| !stem.is_empty())
+        .map(|stem| sanitize_filename(&stem))
+        .unwrap_or_else(|| "pack".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_packwiz_url, pack_stem};
+    use url::Url;
+
+    #[test]
+    fn derives_cache_name_from_last_path_segment() {
+        let url =
+            Url::parse("https://example.com/releases/My%20Pack-1.2.mrpack?token=abc").unwrap();
+        assert_eq!(pack_stem(&url), "My Pack-1.2");
+
+        let url = Url::parse("https://example.com/").unwrap();
+        assert_eq!(pack_stem(&url), "pack");
+    }
+
+    #[test]
+    fn recognizes_packwiz_urls() {
+        assert!(is_packwiz_url("https://example.com/pack/pack.toml"));
+        assert!(!is_packwiz_url("https://example.com/download?id=1"));
+    }
+}
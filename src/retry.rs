@@ -0,0 +1,260 @@
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::io::ErrorKind;
+use std::time::Duration;
+
+pub const DEFAULT_RETRIES: u32 = 3;
+pub const DEFAULT_RETRY_DELAY_MS: u64 = 500;
+
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// Servers asking for a longer pause than this are treated as a failure instead of a wait.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+/// How many times a transient HTTP failure is retried and how long to wait in between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total attempts per request, including the first one.
+    pub max_attempts: u32,
+    /// Wait before the first retry; it doubles with every retry after that.
+    pub base_delay: Duration,
+}
+
+/// A non-success response, kept as an error so callers can decide whether to retry it.
+#[derive(Debug, thiserror::Error)]
+#[error("HTTP {status}")]
+pub struct StatusError {
+    pub status: StatusCode,
+    pub retry_after: Option<Duration>,
+}
+
+impl StatusError {
+    pub fn from_response(response: &Response) -> Self {
+        Self {
+            status: response.status(),
+            retry_after: retry_after(response.headers()),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(retries: u32, base_delay: Duration) -> Self {
+        Self {
+            max_attempts: retries.saturating_add(1),
+            base_delay,
+        }
+    }
+
+    /// Sends `request`, repeating it on connection errors, timeouts and retryable statuses.
+    /// The last response is returned as-is, so callers keep their own status handling.
+    pub async fn send(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let mut attempt = 1;
+        loop {
+            let Some(current) = request.try_clone() else {
+                return request.send().await;
+            };
+
+            let result = current.send().await;
+            let retry_after = match &result {
+                Ok(response) if is_retryable_status(response.status()) => {
+                    retry_after(response.headers())
+                }
+                Err(e) if is_retryable_request_error(e) => None,
+                _ => return result,
+            };
+            let Some(delay) = self.delay(attempt, retry_after) else {
+                return result;
+            };
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// How long to wait after `attempt` failed with `error`, or `None` when it should not be retried.
+    pub fn delay_after(&self, attempt: u32, error: &anyhow::Error) -> Option<Duration> {
+        let retry_after = transient_failure(error)?;
+        self.delay(attempt, retry_after)
+    }
+
+    fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+
+        match retry_after {
+            Some(wait) if wait > MAX_RETRY_AFTER => None,
+            Some(wait) => Some(wait),
+            None => Some(self.backoff(attempt)),
+        }
+    }
+
+    /// Exponential backoff where half of each step is random, so parallel downloads spread out.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let step = self
+            .base_delay
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(MAX_BACKOFF);
+        let half = step / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+}
+
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::REQUEST_TIMEOUT
+            | StatusCode::TOO_EARLY
+            | StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+fn is_retryable_request_error(error: &reqwest::Error) -> bool {
+    match error.status() {
+        Some(status) => is_retryable_status(status),
+        None => error.is_timeout() || error.is_connect() || error.is_request() || error.is_body(),
+    }
+}
+
+fn is_retryable_io_error(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe
+            | ErrorKind::TimedOut
+            | ErrorKind::UnexpectedEof
+            | ErrorKind::Interrupted
+    )
+}
+
+/// Returns the server's requested wait when a transient failure is found in the error chain.
+fn transient_failure(error: &anyhow::Error) -> Option<Option<Duration>> {
+    error.chain().find_map(|cause| {
+        if let Some(e) = cause.downcast_ref::<StatusError>() {
+            is_retryable_status(e.status).then_some(e.retry_after)
+        } else if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            is_retryable_request_error(e).then_some(None)
+        } else if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+            is_retryable_io_error(e).then_some(None)
+        } else {
+            None
+        }
+    })
+}
+
+/// Parses `Retry-After` given either as seconds or as an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RetryPolicy, StatusError, retry_after};
+    use reqwest::StatusCode;
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use std::time::Duration;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy::new(3, Duration::from_millis(400))
+    }
+
+    fn status_error(status: StatusCode, retry_after: Option<Duration>) -> anyhow::Error {
+        anyhow::Error::new(StatusError {
+            status,
+            retry_after,
+        })
+        .context("Download failed [example]")
+    }
+
+    #[test]
+    fn backs_off_exponentially_with_jitter() {
+        let policy = policy();
+
+        for (attempt, step) in [(1, 400), (2, 800), (3, 1600)] {
+            let delay = policy.backoff(attempt);
+            assert!(delay >= Duration::from_millis(step / 2), "{:?}", delay);
+            assert!(delay <= Duration::from_millis(step), "{:?}", delay);
+        }
+        assert!(policy.backoff(30) <= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn retries_only_transient_failures_until_attempts_run_out() {
+        let policy = policy();
+        let unavailable = status_error(StatusCode::SERVICE_UNAVAILABLE, None);
+
+        assert!(policy.delay_after(1, &unavailable).is_some());
+        assert!(policy.delay_after(3, &unavailable).is_some());
+        assert_eq!(policy.delay_after(4, &unavailable), None);
+        assert_eq!(
+            policy.delay_after(1, &status_error(StatusCode::NOT_FOUND, None)),
+            None
+        );
+        assert_eq!(
+            policy.delay_after(1, &anyhow::anyhow!("Hash mismatch")),
+            None
+        );
+
+        let reset = anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::ConnectionReset));
+        assert!(policy.delay_after(1, &reset).is_some());
+        assert_eq!(
+            RetryPolicy::new(0, Duration::ZERO).delay_after(1, &reset),
+            None
+        );
+    }
+
+    #[test]
+    fn honors_retry_after() {
+        let policy = policy();
+
+        assert_eq!(
+            policy.delay_after(
+                1,
+                &status_error(StatusCode::TOO_MANY_REQUESTS, Some(Duration::from_secs(7)))
+            ),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(
+            policy.delay_after(
+                1,
+                &status_error(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    Some(Duration::from_secs(3600))
+                )
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_retry_after_seconds_and_dates() {
+        let headers = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(RETRY_AFTER, HeaderValue::from_static(value));
+            headers
+        };
+
+        assert_eq!(retry_after(&headers("120")), Some(Duration::from_secs(120)));
+        assert_eq!(
+            retry_after(&headers("Wed, 21 Oct 2015 07:28:00 GMT")),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after(&headers("soon")), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+}
@@ -0,0 +1,25 @@
+//! Fixtures shared by the unit tests.
+
+use std::io::Write;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// A fresh directory of its own that is removed when the guard drops, even after a failed assertion.
+pub fn temp_dir() -> TempDir {
+    tempfile::Builder::new()
+        .prefix("mcpacker-test-")
+        .tempdir()
+        .unwrap()
+}
+
+/// Writes a zip, such as a mod jar, holding the given `(name, contents)` entries.
+pub fn write_zip(path: &Path, entries: &[(&str, &str)]) {
+    let mut writer = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+    for (name, contents) in entries {
+        writer
+            .start_file(*name, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(contents.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap();
+}
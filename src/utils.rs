@@ -1,3 +1,39 @@
+use anyhow::{Context, Result};
+use std::path::{Component, Path, PathBuf};
+
+/// CurseForge's site and CDN reject requests that do not look like they come from a browser.
+pub const BROWSER_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+pub fn cache_dir() -> Result<PathBuf> {
+    let project_dirs = directories::ProjectDirs::from("com", "mcpacker", "mcpacker")
+        .context("Failed to determine cache directory")?;
+    let cache_dir = project_dirs.cache_dir();
+    if !cache_dir.exists() {
+        std::fs::create_dir_all(cache_dir)?;
+    }
+    Ok(cache_dir.to_path_buf())
+}
+
+pub fn is_safe_relative_path(path: &Path) -> bool {
+    path.components().next().is_some()
+        && path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Adds every file below `dir` to `files`; a missing directory adds nothing.
+pub fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+}
+
 pub fn sanitize_filename(name: &str) -> String {
     let cleaned: String = name
         .chars()